@@ -8,20 +8,23 @@ use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 use std::collections::HashMap;
 use std::process::Stdio;
 use tokio::process::Command;
 use futures_util::{SinkExt, StreamExt};
 use axum::{
-    extract::{ws::WebSocketUpgrade, State},
+    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, State},
     http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    response::{IntoResponse, Response},
     routing::get,
     Router,
 };
 use tower_http::cors::CorsLayer;
+use tracing::{debug, error, info, warn};
+use unicode_normalization::UnicodeNormalization;
+use tracing_subscriber::{filter::LevelFilter, fmt, prelude::*, reload, Registry};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClipboardItem {
@@ -31,8 +34,22 @@ pub struct ClipboardItem {
     pub created_at: u64,
     pub last_accessed: u64,
     pub access_count: u32,
-    pub source: String,
+    pub source: String, // One of CLIPBOARD_SOURCES
     pub size: usize,
+    #[serde(default)]
+    pub pinned: bool,
+    // 0 (unrated) to 5 stars, set by rate_clipboard_item
+    #[serde(default)]
+    pub rating: u8,
+    // SHA-256 hex of the content, stable across instances unlike the random id
+    #[serde(default)]
+    pub content_hash: String,
+    // Position within the pinned section, set by reorder_pinned_items
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin_order: Option<u32>,
+    // Seconds since created_at; only filled in when a list command asks for it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_secs: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +58,48 @@ pub struct ClipboardDatabase {
     pub max_items: usize,
 }
 
+// Runtime state of the clipboard monitor thread (not persisted)
+#[derive(Debug, Default)]
+pub struct ClipboardMonitorState {
+    pub incognito: bool,
+    pub incognito_ids: Vec<String>, // Items captured while incognito, dropped when it ends
+    pub dirty: bool, // In-memory changes waiting for the debounced saver
+    pub pasted_image_hash: Option<u64>, // Image we put on the clipboard ourselves, not a new copy
+    pub paused: bool, // Capture suspended entirely, nothing is read from the clipboard
+    pub event_filter: Vec<String>, // Content types that emit clipboard-update; empty means all
+    pub pending_source: Option<(String, &'static str)>, // Hash of text we just wrote and the source to record it under
+    pub current_item_id: Option<String>, // Stored item matching the last clipboard content the monitor saw
+    pub resume_baseline: Option<ClipboardBaseline>, // Set on resume, adopted by the monitor on its next tick
+}
+
+// What was on the clipboard when capture resumed, so it isn't recorded as a new copy
+#[derive(Debug)]
+pub struct ClipboardBaseline {
+    pub text: String,
+    pub image_hash: Option<u64>,
+    pub change_count: Option<u64>,
+}
+
+// ClipboardItem::source values: OS copies, app-initiated pastes/copies, and snippet expansions
+const CLIPBOARD_SOURCE_SYSTEM: &str = "system";
+const CLIPBOARD_SOURCE_PASTE: &str = "pathfinder-paste";
+const CLIPBOARD_SOURCE_COPY: &str = "pathfinder-copy";
+const CLIPBOARD_SOURCE_SNIPPET: &str = "snippet";
+const CLIPBOARD_SOURCES: &[&str] = &[
+    CLIPBOARD_SOURCE_SYSTEM,
+    CLIPBOARD_SOURCE_PASTE,
+    CLIPBOARD_SOURCE_COPY,
+    CLIPBOARD_SOURCE_SNIPPET,
+];
+
+// Call before writing text to the clipboard, so the monitor records it under `source` instead of "system"
+fn expect_clipboard_source(app_handle: &tauri::AppHandle, content: &str, source: &'static str) {
+    let monitor = app_handle.state::<Arc<Mutex<ClipboardMonitorState>>>();
+    if let Ok(mut monitor) = monitor.lock() {
+        monitor.pending_source = Some((content_hash(content), source));
+    };
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileItem {
     pub name: String,
@@ -49,13 +108,172 @@ pub struct FileItem {
     pub size: u64,
     pub modified: u64,
     pub is_app: bool,
+    // Older index files predate these, so they default when loading
+    #[serde(default)]
+    pub created: u64,
+    #[serde(default)]
+    pub is_dir: bool,
+    #[serde(default)]
+    pub readonly: bool,
+    // Finder tags on macOS; empty elsewhere
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    // Only set on search results whose name collides with another result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_path: Option<String>,
+    // Seconds since modified; only filled in when a list command asks for it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_secs: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileSearchDatabase {
     pub files: Vec<FileItem>,
     pub apps: Vec<FileItem>,
-    pub last_indexed: u64,
+    pub last_indexed: u64, // Most recent of apps_indexed / files_indexed
+    #[serde(default)]
+    pub apps_indexed: u64,
+    #[serde(default)]
+    pub files_indexed: u64,
+    // app_identity -> position in `apps`, so one app found through several dirs is listed once.
+    // Only valid while apps are being added; reindex_apps rebuilds it
+    #[serde(skip)]
+    app_keys: std::collections::HashMap<String, usize>,
+}
+
+// Copy of the app list behind its own lock, so app search never waits on a file reindex
+#[derive(Debug, Default)]
+pub struct AppsCache {
+    pub apps: Vec<FileItem>,
+}
+
+// Timings from the last index run and search, for tuning index settings
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PerfMetrics {
+    pub last_index_duration_ms: u64,
+    pub last_index_entries: usize,
+    pub index_entries_per_sec: f64,
+    pub last_indexed_at: u64,
+    pub last_search_query: String,
+    pub last_search_duration_us: u64,
+    pub last_search_results: usize,
+    pub search_count: u64,
+}
+
+// An extra folder indexed alongside the default user folders
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexSource {
+    pub path: String,
+    // Shared folders: reindexing only merges new entries in, verify/compact never touch them
+    #[serde(default)]
+    pub read_only: bool,
+    // How deep to walk, overriding DEFAULT_INDEX_DEPTH. A source whose path is one of the
+    // default folders sets that folder's depth instead of being walked twice
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+}
+
+impl IndexSource {
+    // The configured path may be written as ~/Projects or %USERPROFILE%\Shared
+    fn root(&self) -> PathBuf {
+        expand_path(&self.path)
+    }
+}
+
+// User-defined command run on a search result, e.g. `code "{dir}"`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuickAction {
+    pub name: String,
+    // {path}, {dir} and {name} are substituted per argument; no shell is involved
+    pub command_template: String,
+}
+
+// User-editable settings persisted to settings.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    // Extra directories scanned for applications on top of the platform defaults
+    pub app_dirs: Vec<String>,
+    // Optional hotkey (e.g. "Ctrl+Shift+V") that pastes an older history item directly
+    pub quick_paste_shortcut: Option<String>,
+    // Which item the quick paste shortcut pastes, by recency (0 = current clipboard)
+    pub quick_paste_index: usize,
+    // Active clipboard history profile; changed through switch_profile
+    pub clipboard_profile: String,
+    // App paths left in the index but filtered out of results (hide_app/unhide_app)
+    pub hidden_apps: Vec<String>,
+    // Keep pinned items in place when their content is copied again
+    pub freeze_pinned: bool,
+    // How captured images are stored: "png" (lossless), "jpeg" or "webp"
+    pub image_format: String,
+    // JPEG quality, 1-100
+    pub image_quality: u8,
+    // Match "cafe" against "café" in clipboard and file search
+    pub fold_diacritics: bool,
+    // How many of the newest items a copy is checked against for duplicates (0 = whole history)
+    pub dedup_window: usize,
+    // Re-copying existing content moves it back to the top instead of leaving it in place
+    pub move_to_top_on_recopy: bool,
+    // Folders indexed on top of Documents/Downloads/Desktop/Pictures
+    pub index_sources: Vec<IndexSource>,
+    // Run on a result through run_quick_action
+    pub quick_actions: Vec<QuickAction>,
+    // Rebuild the file index in the background this often (0 = disabled)
+    pub auto_reindex_interval_secs: u64,
+    // Ignore text that only differs in whitespace from a capture this recent (0 = disabled)
+    pub coalesce_window_ms: u64,
+    // When non-empty, only files with these extensions (e.g. "pdf", "docx") are indexed,
+    // at any depth; folders and everything else are skipped
+    pub index_extension_allowlist: Vec<String>,
+    // Move items pushed out past max_items to the archive (search_archive) instead of dropping them.
+    // Off by default since the archive is never trimmed
+    pub archive_evicted: bool,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            app_dirs: Vec::new(),
+            quick_paste_shortcut: None,
+            quick_paste_index: 1,
+            clipboard_profile: DEFAULT_CLIPBOARD_PROFILE.to_string(),
+            hidden_apps: Vec::new(),
+            freeze_pinned: false,
+            image_format: "png".to_string(),
+            image_quality: 80,
+            fold_diacritics: false,
+            dedup_window: 50,
+            move_to_top_on_recopy: false,
+            index_sources: Vec::new(),
+            quick_actions: Vec::new(),
+            auto_reindex_interval_secs: 0,
+            coalesce_window_ms: 2000,
+            index_extension_allowlist: Vec::new(),
+            archive_evicted: false,
+        }
+    }
+}
+
+// "image" matches "image/png", "url" matches "url"
+fn content_type_matches(content_type: &str, wanted: &str) -> bool {
+    content_type == wanted
+        || content_type.strip_prefix(wanted).is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn content_hash(content: &str) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+// Lowercase for search, optionally stripping accents so query and content compare on base letters
+fn fold_for_search(text: &str, fold_diacritics: bool) -> String {
+    if !fold_diacritics {
+        return text.to_lowercase();
+    }
+    text.nfd()
+        .filter(|c| !unicode_normalization::char::is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
 }
 
 impl ClipboardDatabase {
@@ -66,25 +284,194 @@ impl ClipboardDatabase {
         }
     }
 
-    fn add_item(&mut self, item: ClipboardItem) {
+    // With freeze_pinned, re-copying a pinned item leaves its timestamps (and so its position) alone.
+    // Only the newest dedup_window items are checked, so large histories don't scan on every copy.
+    // While incognito (`incognito_ids` is Some) persisted items are left untouched: re-copies don't
+    // bump them and only in-memory captures are evicted, so the list may grow past max_items until
+    // incognito ends. Returns the items evicted to stay within max_items
+    fn add_item(
+        &mut self,
+        mut item: ClipboardItem,
+        settings: &AppSettings,
+        incognito_ids: Option<&[String]>,
+    ) -> Vec<ClipboardItem> {
+        item.content_hash = content_hash(&item.content);
+        let window = if settings.dedup_window == 0 { self.items.len() } else { settings.dedup_window };
+        let persisted = |item: &ClipboardItem| incognito_ids.is_some_and(|ids| !ids.contains(&item.id));
         // Check if item already exists
-        if let Some(existing) = self.items.iter_mut().find(|i| i.content == item.content) {
-            existing.last_accessed = item.created_at;
+        if let Some(index) = self.items.iter().take(window).position(|i| i.content == item.content) {
+            if persisted(&self.items[index]) {
+                return Vec::new();
+            }
+            let existing = &mut self.items[index];
             existing.access_count += 1;
-            return;
+            if existing.pinned && settings.freeze_pinned {
+                return Vec::new();
+            }
+            existing.last_accessed = item.created_at;
+            if settings.move_to_top_on_recopy {
+                // Treated as a fresh copy of the same item, keeping its id and stats
+                existing.created_at = item.created_at;
+                let existing = self.items.remove(index);
+                self.items.insert(0, existing);
+            }
+            return Vec::new();
         }
 
         // Add new item at the beginning
         self.items.insert(0, item);
 
-        // Maintain max items limit
-        if self.items.len() > self.max_items {
-            self.items.truncate(self.max_items);
+        // Maintain max items limit, evicting the oldest unpinned items first
+        let mut evicted = Vec::new();
+        while self.items.len() > self.max_items {
+            match self.items.iter().rposition(|i| !i.pinned && !persisted(i)) {
+                Some(index) => evicted.push(self.items.remove(index)),
+                None => break,
+            }
+        }
+        evicted
+    }
+
+    // Collapse duplicate items into one survivor per group: a pinned item if the group has one,
+    // otherwise the most recently used. With normalize, text differing only in case or trailing
+    // whitespace counts as a duplicate. Pinned items are never removed. Returns how many were removed
+    fn dedup(&mut self, normalize: bool) -> usize {
+        let keys: Vec<String> = self
+            .items
+            .iter()
+            .map(|item| {
+                if normalize && !item.content_type.starts_with("image") {
+                    item.content.trim_end().to_lowercase()
+                } else {
+                    item.content.clone()
+                }
+            })
+            .collect();
+
+        let mut survivors: HashMap<&str, usize> = HashMap::new();
+        for (index, key) in keys.iter().enumerate() {
+            let item = &self.items[index];
+            survivors
+                .entry(key)
+                .and_modify(|survivor| {
+                    let current = &self.items[*survivor];
+                    if (item.pinned, item.last_accessed) > (current.pinned, current.last_accessed) {
+                        *survivor = index;
+                    }
+                })
+                .or_insert(index);
+        }
+
+        let mut remove = vec![false; self.items.len()];
+        for (index, key) in keys.iter().enumerate() {
+            let survivor = survivors[key.as_str()];
+            if survivor == index || self.items[index].pinned {
+                continue;
+            }
+            let (access_count, last_accessed, rating) = {
+                let duplicate = &self.items[index];
+                (duplicate.access_count, duplicate.last_accessed, duplicate.rating)
+            };
+            let survivor = &mut self.items[survivor];
+            survivor.access_count += access_count;
+            survivor.last_accessed = survivor.last_accessed.max(last_accessed);
+            survivor.rating = survivor.rating.max(rating);
+            remove[index] = true;
+        }
+
+        let mut index = 0;
+        self.items.retain(|_| {
+            index += 1;
+            !remove[index - 1]
+        });
+        remove.iter().filter(|removed| **removed).count()
+    }
+
+    fn set_pinned(&mut self, id: &str, pinned: bool) -> bool {
+        match self.items.iter_mut().find(|i| i.id == id) {
+            Some(item) => {
+                item.pinned = pinned;
+                if !pinned {
+                    item.pin_order = None;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Add an older item (e.g. from an import) behind the existing history
+    fn append_item(&mut self, mut item: ClipboardItem) -> bool {
+        if self.items.len() >= self.max_items || self.items.iter().any(|i| i.content == item.content) {
+            return false;
         }
+        item.content_hash = content_hash(&item.content);
+
+        self.items.push(item);
+        true
     }
 
+    // Pinned items first in their curated order (unordered pins after), then the rest as stored
     fn get_items(&self) -> Vec<ClipboardItem> {
-        self.items.clone()
+        let mut items = self.items.clone();
+        // sort_by_key is stable, so items keep their relative order within each group
+        items.sort_by_key(|item| match (item.pinned, item.pin_order) {
+            (true, Some(order)) => (0, order),
+            (true, None) => (1, 0),
+            (false, _) => (2, 0),
+        });
+        items
+    }
+
+    fn reorder_pinned(&mut self, ordered_ids: &[String]) -> Result<(), String> {
+        for id in ordered_ids {
+            match self.items.iter().find(|i| i.id == *id) {
+                Some(item) if item.pinned => {}
+                Some(_) => return Err(format!("Clipboard item is not pinned: {}", id)),
+                None => return Err(format!("Clipboard item not found: {}", id)),
+            }
+        }
+
+        for item in self.items.iter_mut().filter(|i| i.pinned) {
+            item.pin_order = ordered_ids
+                .iter()
+                .position(|id| *id == item.id)
+                .map(|index| index as u32);
+        }
+        Ok(())
+    }
+
+    // AND-combine the text query, content type and time range, newest first
+    fn search(
+        &self,
+        query: Option<&str>,
+        content_type: Option<&str>,
+        since: Option<u64>,
+        until: Option<u64>,
+        fold_diacritics: bool,
+    ) -> Vec<ClipboardItem> {
+        let query_lower = query
+            .map(|q| fold_for_search(q, fold_diacritics))
+            .filter(|q| !q.is_empty());
+
+        let mut results: Vec<ClipboardItem> = self
+            .items
+            .iter()
+            .filter(|item| content_type.is_none_or(|t| content_type_matches(&item.content_type, t)))
+            .filter(|item| since.is_none_or(|since| item.last_accessed >= since))
+            .filter(|item| until.is_none_or(|until| item.last_accessed <= until))
+            .filter(|item| {
+                // Image content is base64, so a text query would only produce noise matches
+                query_lower.as_ref().is_none_or(|q| {
+                    !item.content_type.starts_with("image/")
+                        && fold_for_search(&item.content, fold_diacritics).contains(q)
+                })
+            })
+            .cloned()
+            .collect();
+
+        results.sort_by_key(|item| std::cmp::Reverse(item.last_accessed));
+        results
     }
 
     fn update_access(&mut self, id: &str) {
@@ -106,70 +493,374 @@ impl ClipboardDatabase {
     }
 }
 
+// Give results that share a file name a short parent directory so the UI can tell them apart
+fn disambiguate_results(results: &mut [FileItem]) {
+    let mut name_counts: HashMap<String, usize> = HashMap::new();
+    for item in results.iter() {
+        *name_counts.entry(item.name.to_lowercase()).or_insert(0) += 1;
+    }
+
+    let home_dir = dirs::home_dir();
+    for item in results.iter_mut() {
+        if name_counts.get(&item.name.to_lowercase()).copied().unwrap_or(0) < 2 {
+            continue;
+        }
+
+        let Some(parent) = std::path::Path::new(&item.path).parent() else {
+            continue;
+        };
+        let display = match home_dir.as_ref().and_then(|home| parent.strip_prefix(home).ok()) {
+            Some(relative) => PathBuf::from("~").join(relative).to_string_lossy().to_string(),
+            None => parent.to_string_lossy().to_string(),
+        };
+        item.display_path = Some(display);
+    }
+}
+
 impl FileSearchDatabase {
     fn new() -> Self {
         Self {
             files: Vec::new(),
             apps: Vec::new(),
             last_indexed: 0,
+            apps_indexed: 0,
+            files_indexed: 0,
+            app_keys: std::collections::HashMap::new(),
         }
     }
 
     fn add_file(&mut self, file: FileItem) {
         if file.is_app {
-            self.apps.push(file);
+            let key = app_identity(Path::new(&file.path));
+            match self.app_keys.get(&key) {
+                // Keep the shallower path, e.g. /Applications/Safari.app over its /System target
+                Some(&index) => {
+                    let depth = |path: &str| Path::new(path).components().count();
+                    if depth(&file.path) < depth(&self.apps[index].path) {
+                        self.apps[index] = file;
+                    }
+                }
+                None => {
+                    self.app_keys.insert(key, self.apps.len());
+                    self.apps.push(file);
+                }
+            }
         } else {
             self.files.push(file);
         }
     }
 
-    fn search_files(&self, query: &str) -> Vec<FileItem> {
+    // `root` restricts results to entries below that folder
+    fn search_files(
+        &self,
+        query: &str,
+        hidden_apps: &[String],
+        fold_diacritics: bool,
+        root: Option<&Path>,
+    ) -> Vec<FileItem> {
         let mut results = Vec::new();
-        let query_lower = query.to_lowercase();
+
+        // "tag:important report" keeps items tagged "important", then matches the rest by name
+        let (tag_filter, query) = match query.trim_start().strip_prefix("tag:") {
+            Some(rest) => {
+                let (tag, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                (Some(fold_for_search(tag, fold_diacritics)), rest.trim())
+            }
+            None => (None, query),
+        };
+        let query_lower = fold_for_search(query, fold_diacritics);
+
+        // Queries like "*.rs" or "report_??.pdf" match whole names as a glob;
+        // anything else (or an invalid pattern) is a plain substring search
+        let glob_pattern = query
+            .contains(['*', '?', '['])
+            .then(|| glob::Pattern::new(&fold_for_search(query.trim(), fold_diacritics)).ok())
+            .flatten();
+        let glob_options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        let matches = |item: &FileItem| {
+            let tagged = tag_filter
+                .as_ref()
+                .is_none_or(|tag| item.tags.iter().any(|t| fold_for_search(t, fold_diacritics) == *tag));
+            let in_root = root.is_none_or(|root| {
+                let path = Path::new(&item.path);
+                path != root && path.starts_with(root)
+            });
+            let name = fold_for_search(&item.name, fold_diacritics);
+            tagged
+                && in_root
+                && match &glob_pattern {
+                    Some(pattern) => pattern.matches_with(&name, glob_options),
+                    None => name.contains(&query_lower),
+                }
+        };
 
         // Search in apps first
         for app in &self.apps {
-            if app.name.to_lowercase().contains(&query_lower) {
+            if matches(app) && !hidden_apps.contains(&app.path) {
                 results.push(app.clone());
             }
         }
 
         // Then search in files
         for file in &self.files {
-            if file.name.to_lowercase().contains(&query_lower) {
+            if matches(file) {
                 results.push(file.clone());
             }
         }
 
         // Limit results to prevent UI lag
         results.truncate(50);
+        disambiguate_results(&mut results);
         results
     }
 
-    fn get_apps(&self) -> Vec<FileItem> {
-        self.apps.clone()
+    // Drop entries whose files are gone and collapse duplicates of the same canonical path
+    // Entries under a protected (read-only source) root are kept as-is
+    fn compact(&mut self, protected: &[PathBuf]) {
+        let compact_list = |items: &mut Vec<FileItem>| {
+            let mut seen = std::collections::HashSet::new();
+            items.retain(|item| {
+                if is_under_any(&item.path, protected) {
+                    return true;
+                }
+                let path = PathBuf::from(&item.path);
+                match fs::canonicalize(&path) {
+                    Ok(canonical) => seen.insert(canonical),
+                    Err(_) => false, // Path no longer exists
+                }
+            });
+        };
+
+        compact_list(&mut self.apps);
+        compact_list(&mut self.files);
+    }
+
+    fn get_apps(&self, hidden_apps: &[String]) -> Vec<FileItem> {
+        self.apps
+            .iter()
+            .filter(|app| !hidden_apps.contains(&app.path))
+            .cloned()
+            .collect()
     }
 
     fn get_recent_files(&self) -> Vec<FileItem> {
-        let mut recent_files = self.files.clone();
-        recent_files.sort_by(|a, b| b.modified.cmp(&a.modified));
+        // A folder's mtime moves whenever its contents change, so keep this list to files
+        let mut recent_files: Vec<FileItem> = self.files.iter().filter(|f| !f.is_dir).cloned().collect();
+        recent_files.sort_by_key(|file| std::cmp::Reverse(file.modified));
         recent_files.truncate(20);
         recent_files
     }
 }
 
-fn get_db_path(app_handle: &tauri::AppHandle) -> PathBuf {
+// Rough content type for a captured string
+fn classify_content(content: &str) -> &'static str {
+    let trimmed = content.trim();
+    let is_url = (trimmed.starts_with("http://") || trimmed.starts_with("https://"))
+        && !trimmed.contains(char::is_whitespace);
+    if is_url {
+        "url"
+    } else {
+        "text"
+    }
+}
+
+const DEFAULT_CLIPBOARD_PROFILE: &str = "default";
+
+// The default profile keeps the original file name so existing history carries over
+fn get_profile_db_path(app_handle: &tauri::AppHandle, profile: &str) -> PathBuf {
+    let file_name = if profile == DEFAULT_CLIPBOARD_PROFILE {
+        "clipboard_history.json".to_string()
+    } else {
+        format!("clipboard_history_{}.json", profile)
+    };
+    app_handle
+        .path()
+        .app_data_dir()
+        .expect("Failed to get app data dir")
+        .join(file_name)
+}
+
+// Cold store for items aged out of the active profile's history. One JSON item per line,
+// like the NDJSON export, so archiving an item is an append rather than a rewrite; hence
+// .ndjson rather than a clipboard_archive.json array
+fn get_archive_path(app_handle: &tauri::AppHandle) -> PathBuf {
+    let profile = app_handle
+        .try_state::<Arc<Mutex<AppSettings>>>()
+        .and_then(|settings| settings.lock().ok().map(|s| s.clipboard_profile.clone()))
+        .unwrap_or_else(|| DEFAULT_CLIPBOARD_PROFILE.to_string());
+    let file_name = if profile == DEFAULT_CLIPBOARD_PROFILE {
+        "clipboard_archive.ndjson".to_string()
+    } else {
+        format!("clipboard_archive_{}.ndjson", profile)
+    };
     app_handle
         .path()
         .app_data_dir()
         .expect("Failed to get app data dir")
-        .join("clipboard_history.json")
+        .join(file_name)
+}
+
+// Incognito captures stay in memory only, so they are never archived either
+fn archive_evicted_items(
+    app_handle: &tauri::AppHandle,
+    settings: &AppSettings,
+    items: &[ClipboardItem],
+) {
+    use std::io::Write;
+
+    if !settings.archive_evicted || items.is_empty() {
+        return;
+    }
+    let monitor = app_handle.state::<Arc<Mutex<ClipboardMonitorState>>>();
+    if monitor.lock().map(|m| m.incognito).unwrap_or(true) {
+        return;
+    }
+
+    let mut lines = String::new();
+    for item in items {
+        if let Ok(json) = serde_json::to_string(item) {
+            lines.push_str(&json);
+            lines.push('\n');
+        }
+    }
+    let written = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(get_archive_path(app_handle))
+        .and_then(|mut file| file.write_all(lines.as_bytes()));
+    if let Err(e) = written {
+        warn!("⚠️  Failed to archive {} clipboard items: {}", items.len(), e);
+    }
+}
+
+// Path of the history file for the active profile
+fn get_db_path(app_handle: &tauri::AppHandle) -> PathBuf {
+    let profile = app_handle
+        .try_state::<Arc<Mutex<AppSettings>>>()
+        .and_then(|settings| settings.lock().ok().map(|s| s.clipboard_profile.clone()))
+        .unwrap_or_else(|| DEFAULT_CLIPBOARD_PROFILE.to_string());
+    get_profile_db_path(app_handle, &profile)
+}
+
+// Profile names become part of a file name, so keep them short and plain
+fn validate_profile_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid profile name '{}': use up to 32 letters, digits, '-' or '_'",
+            name
+        ))
+    }
+}
+
+// Writes that fail are retried this many times in total, backing off a little longer each time
+const SAVE_RETRY_ATTEMPTS: u32 = 3;
+const SAVE_RETRY_BACKOFF_MS: u64 = 100;
+
+// Rides out transient failures like a file briefly locked by antivirus or a momentarily full disk
+fn write_with_retry(path: &PathBuf, contents: &str) -> Result<(), String> {
+    let mut attempt = 1;
+    loop {
+        match fs::write(path, contents) {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < SAVE_RETRY_ATTEMPTS => {
+                debug!("Write to {} failed (attempt {}): {}", path.display(), attempt, e);
+                std::thread::sleep(std::time::Duration::from_millis(SAVE_RETRY_BACKOFF_MS * attempt as u64));
+                attempt += 1;
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
+
+// Tell the frontend a background save gave up, so history loss isn't silent
+fn report_persistence_error(app_handle: &tauri::AppHandle, target: &str, error: &str) {
+    error!("❌ Failed to save {}: {}", target, error);
+    let _ = app_handle.emit(
+        "persistence-error",
+        serde_json::json!({ "target": target, "error": error }),
+    );
 }
 
 fn save_db(db: &ClipboardDatabase, path: &PathBuf) -> Result<(), String> {
     let json = serde_json::to_string_pretty(db).map_err(|e| e.to_string())?;
-    fs::write(path, json).map_err(|e| e.to_string())?;
-    Ok(())
+    write_with_retry(path, &json)
+}
+
+// Save the clipboard DB unless incognito mode is keeping history in memory only
+fn persist_clipboard_db(app_handle: &tauri::AppHandle, db: &ClipboardDatabase) -> Result<(), String> {
+    let monitor = app_handle.state::<Arc<Mutex<ClipboardMonitorState>>>();
+    if monitor.lock().map_err(|e| e.to_string())?.incognito {
+        return Ok(());
+    }
+
+    save_db(db, &get_db_path(app_handle))
+}
+
+// How often the background saver writes out pending clipboard changes
+const CLIPBOARD_SAVE_INTERVAL_MS: u64 = 2000;
+
+// Queue a save for the debounced saver instead of writing on every change
+fn mark_clipboard_dirty(app_handle: &tauri::AppHandle) {
+    let monitor = app_handle.state::<Arc<Mutex<ClipboardMonitorState>>>();
+    if let Ok(mut monitor) = monitor.lock() {
+        monitor.dirty = true;
+    };
+}
+
+// Write pending changes now; also used by the saver thread and on exit
+fn flush_pending_clipboard(app_handle: &tauri::AppHandle) -> Result<(), String> {
+    let pending = {
+        let monitor = app_handle.state::<Arc<Mutex<ClipboardMonitorState>>>();
+        let mut monitor = monitor.lock().map_err(|e| e.to_string())?;
+        std::mem::take(&mut monitor.dirty)
+    };
+    if !pending {
+        return Ok(());
+    }
+
+    let db = app_handle.state::<Arc<Mutex<ClipboardDatabase>>>();
+    let db = db.lock().map_err(|e| e.to_string())?;
+    let result = persist_clipboard_db(app_handle, &db);
+    if result.is_err() {
+        // Keep the changes pending so the next flush tries again
+        mark_clipboard_dirty(app_handle);
+    }
+    result
+}
+
+fn start_clipboard_saver(app_handle: tauri::AppHandle) {
+    std::thread::spawn(move || {
+        // Only the first failure in a row is reported; the saver keeps retrying every interval
+        let mut failing = false;
+        loop {
+            std::thread::sleep(std::time::Duration::from_millis(CLIPBOARD_SAVE_INTERVAL_MS));
+            match flush_pending_clipboard(&app_handle) {
+                Err(e) if !failing => {
+                    failing = true;
+                    report_persistence_error(&app_handle, "clipboard history", &e);
+                }
+                Err(e) => debug!("Clipboard history still not saving: {}", e),
+                Ok(()) if failing => {
+                    failing = false;
+                    info!("💾 Clipboard history saving again");
+                }
+                Ok(()) => {}
+            }
+        }
+    });
+}
+
+#[tauri::command]
+fn flush_clipboard_db(app_handle: tauri::AppHandle) -> Result<(), String> {
+    flush_pending_clipboard(&app_handle)
 }
 
 fn load_db(path: &PathBuf) -> Result<ClipboardDatabase, String> {
@@ -178,7 +869,11 @@ fn load_db(path: &PathBuf) -> Result<ClipboardDatabase, String> {
     }
 
     let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    let db: ClipboardDatabase = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    let mut db: ClipboardDatabase = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    // Histories saved before content hashes existed
+    for item in db.items.iter_mut().filter(|item| item.content_hash.is_empty()) {
+        item.content_hash = content_hash(&item.content);
+    }
     Ok(db)
 }
 
@@ -192,8 +887,7 @@ fn get_file_search_db_path(app_handle: &tauri::AppHandle) -> PathBuf {
 
 fn save_file_db(db: &FileSearchDatabase, path: &PathBuf) -> Result<(), String> {
     let json = serde_json::to_string_pretty(db).map_err(|e| e.to_string())?;
-    fs::write(path, json).map_err(|e| e.to_string())?;
-    Ok(())
+    write_with_retry(path, &json)
 }
 
 fn load_file_db(path: &PathBuf) -> Result<FileSearchDatabase, String> {
@@ -206,133 +900,607 @@ fn load_file_db(path: &PathBuf) -> Result<FileSearchDatabase, String> {
     Ok(db)
 }
 
-fn get_file_extension(path: &PathBuf) -> String {
+fn get_settings_path(app_handle: &tauri::AppHandle) -> PathBuf {
+    app_handle
+        .path()
+        .app_data_dir()
+        .expect("Failed to get app data dir")
+        .join("settings.json")
+}
+
+fn save_settings(settings: &AppSettings, path: &PathBuf) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    write_with_retry(path, &json)
+}
+
+fn load_settings(path: &PathBuf) -> Result<AppSettings, String> {
+    if !path.exists() {
+        return Ok(AppSettings::default());
+    }
+
+    let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let settings: AppSettings = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    Ok(settings)
+}
+
+fn get_session_state_path(app_handle: &tauri::AppHandle) -> PathBuf {
+    app_handle
+        .path()
+        .app_data_dir()
+        .expect("Failed to get app data dir")
+        .join("session_state.json")
+}
+
+// Expand `~`, POSIX `$VAR`/`${VAR}` and Windows `%VAR%` in user-supplied paths.
+// Unknown variables are left untouched so the resulting error names them
+fn expand_path(raw: &str) -> PathBuf {
+    let raw = raw.trim();
+
+    // Home directory
+    let mut expanded = String::with_capacity(raw.len());
+    let rest = if raw == "~" || raw.starts_with("~/") || raw.starts_with("~\\") {
+        match dirs::home_dir() {
+            Some(home) => {
+                expanded.push_str(&home.to_string_lossy());
+                &raw[1..]
+            }
+            None => raw,
+        }
+    } else {
+        raw
+    };
+
+    let chars: Vec<char> = rest.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '$' => {
+                // ${VAR} or $VAR
+                let (name, end) = if chars.get(i + 1) == Some(&'{') {
+                    match chars[i + 2..].iter().position(|&c| c == '}') {
+                        Some(len) => (chars[i + 2..i + 2 + len].iter().collect::<String>(), i + 3 + len),
+                        None => (String::new(), i + 1),
+                    }
+                } else {
+                    let len = chars[i + 1..]
+                        .iter()
+                        .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                        .count();
+                    (chars[i + 1..i + 1 + len].iter().collect::<String>(), i + 1 + len)
+                };
+
+                match (name.is_empty(), std::env::var(&name)) {
+                    (false, Ok(value)) => expanded.push_str(&value),
+                    _ => expanded.extend(&chars[i..end.max(i + 1)]),
+                }
+                i = end.max(i + 1);
+            }
+            '%' => {
+                // %VAR%
+                match chars[i + 1..].iter().position(|&c| c == '%') {
+                    Some(len) if len > 0 => {
+                        let name: String = chars[i + 1..i + 1 + len].iter().collect();
+                        match std::env::var(&name) {
+                            Ok(value) => expanded.push_str(&value),
+                            Err(_) => expanded.extend(&chars[i..i + 2 + len]),
+                        }
+                        i += len + 2;
+                    }
+                    _ => {
+                        expanded.push('%');
+                        i += 1;
+                    }
+                }
+            }
+            c => {
+                expanded.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    PathBuf::from(expanded)
+}
+
+fn get_file_extension(path: &Path) -> String {
     path.extension()
         .and_then(|ext| ext.to_str())
         .unwrap_or("")
         .to_lowercase()
 }
 
-fn is_app_file(path: &PathBuf) -> bool {
+fn is_app_file(path: &Path) -> bool {
     let extension = get_file_extension(path);
     match extension.as_str() {
         "app" => true, // macOS
         "exe" | "msi" => true, // Windows
-        "deb" | "rpm" | "AppImage" => true, // Linux
+        "deb" | "rpm" | "appimage" | "desktop" => true, // Linux
         _ => false,
     }
 }
 
-fn index_applications() -> Vec<FileItem> {
-    let mut apps = Vec::new();
-    
-    // Common application directories
-    let app_dirs = if cfg!(target_os = "macos") {
-        vec![
+// Common application directories, including per-user install locations
+fn default_app_dirs() -> Vec<PathBuf> {
+    let home_dir = dirs::home_dir();
+
+    if cfg!(target_os = "macos") {
+        let mut app_dirs = vec![
             PathBuf::from("/Applications"),
             PathBuf::from("/System/Applications"),
             PathBuf::from("/System/Library/CoreServices"),
-        ]
+        ];
+        if let Some(home) = home_dir {
+            app_dirs.push(home.join("Applications"));
+        }
+        app_dirs
     } else if cfg!(target_os = "windows") {
-        vec![
-            PathBuf::from("C:\\Program Files"),
-            PathBuf::from("C:\\Program Files (x86)"),
-            PathBuf::from("C:\\Users\\%USERNAME%\\AppData\\Local\\Programs"),
-        ]
+        let env_dir = |var: &str, fallback: &str| {
+            std::env::var_os(var)
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(fallback))
+        };
+        let mut app_dirs = vec![
+            env_dir("ProgramFiles", "C:\\Program Files"),
+            env_dir("ProgramFiles(x86)", "C:\\Program Files (x86)"),
+        ];
+        // Apps installed without admin rights (VS Code user setup, Discord, ...)
+        if let Some(local_app_data) = std::env::var_os("LOCALAPPDATA") {
+            app_dirs.push(PathBuf::from(local_app_data).join("Programs"));
+        }
+        app_dirs
     } else {
-        vec![
+        let mut app_dirs = vec![
             PathBuf::from("/usr/share/applications"),
             PathBuf::from("/usr/local/share/applications"),
             PathBuf::from("/var/lib/snapd/desktop/applications"),
-        ]
-    };
-
-    for app_dir in app_dirs {
-        if app_dir.exists() {
-            for entry in WalkDir::new(&app_dir)
-                .max_depth(3)
-                .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                let path = entry.path();                if is_app_file(&path.to_path_buf()) {
-                    if let (Ok(metadata), Some(name)) = (path.metadata(), path.file_name().and_then(|n| n.to_str())) {
-                        let modified = metadata
-                            .modified()
-                            .unwrap_or(SystemTime::UNIX_EPOCH)
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap_or_default()
-                            .as_secs();
-
-                        apps.push(FileItem {
-                            name: name.to_string(),
-                            path: path.to_string_lossy().to_string(),
-                            file_type: get_file_extension(&path.to_path_buf()),
-                            size: metadata.len(),
-                            modified,
-                            is_app: true,
-                        });
-                    }
-                }
-            }
+            PathBuf::from("/var/lib/flatpak/exports/share/applications"),
+        ];
+        if let Some(home) = home_dir {
+            app_dirs.push(home.join(".local/share/applications"));
+            app_dirs.push(home.join(".local/share/flatpak/exports/share/applications"));
         }
+        app_dirs
     }
+}
 
-    apps
+fn system_time_secs(time: std::io::Result<SystemTime>) -> u64 {
+    time.unwrap_or(SystemTime::UNIX_EPOCH)
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
-fn index_user_files() -> Vec<FileItem> {
-    let mut files = Vec::new();
+fn file_item_from_metadata(path: &Path, name: &str, metadata: &fs::Metadata, is_app: bool) -> FileItem {
+    let is_dir = metadata.is_dir();
+    FileItem {
+        name: name.to_string(),
+        path: path.to_string_lossy().to_string(),
+        // .app bundles are directories too, but they should keep their extension
+        file_type: if is_dir && !is_app { "folder".to_string() } else { get_file_extension(path) },
+        size: if is_dir { 0 } else { metadata.len() },
+        modified: system_time_secs(metadata.modified()),
+        is_app,
+        // Not every filesystem records a creation time; those report 0
+        created: system_time_secs(metadata.created()),
+        is_dir,
+        readonly: metadata.permissions().readonly(),
+        tags: read_finder_tags(path),
+        display_path: None,
+        age_secs: None,
+    }
+}
+
+// Finder keeps tags as a binary plist of "Name\n<color>" strings in an xattr
+#[cfg(target_os = "macos")]
+fn read_finder_tags(path: &Path) -> Vec<String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return Vec::new();
+    };
+    let name = c"com.apple.metadata:_kMDItemUserTags";
+
+    // First call sizes the buffer; most files have no tags and stop here
+    let size = unsafe { libc::getxattr(c_path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0, 0, 0) };
+    if size <= 0 {
+        return Vec::new();
+    }
+    let mut buf = vec![0u8; size as usize];
+    let read = unsafe {
+        libc::getxattr(c_path.as_ptr(), name.as_ptr(), buf.as_mut_ptr().cast(), buf.len(), 0, 0)
+    };
+    if read <= 0 {
+        return Vec::new();
+    }
+    buf.truncate(read as usize);
+
+    plist::from_bytes::<Vec<String>>(&buf)
+        .map(|tags| {
+            tags.into_iter()
+                .map(|tag| tag.split('\n').next().unwrap_or_default().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn read_finder_tags(_path: &Path) -> Vec<String> {
+    Vec::new()
+}
+
+// What makes two app entries the same app: the bundle id on macOS, otherwise the
+// symlink-resolved path
+fn app_identity(path: &Path) -> String {
+    #[cfg(target_os = "macos")]
+    {
+        let bundle_id = plist::Value::from_file(path.join("Contents").join("Info.plist"))
+            .ok()
+            .and_then(|info| {
+                info.as_dictionary()?
+                    .get("CFBundleIdentifier")?
+                    .as_string()
+                    .map(str::to_string)
+            });
+        if let Some(bundle_id) = bundle_id {
+            return format!("bundle:{}", bundle_id);
+        }
+    }
+    let resolved = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    format!("path:{}", resolved.to_string_lossy())
+}
+
+fn index_applications(extra_dirs: &[String]) -> Vec<FileItem> {
+    let mut apps = Vec::new();
     
-    // Get user home directory
-    if let Some(home_dir) = dirs::home_dir() {
-        let common_dirs = vec![
-            home_dir.join("Documents"),
-            home_dir.join("Downloads"),
-            home_dir.join("Desktop"),
-            home_dir.join("Pictures"),
-        ];
+    let mut app_dirs = default_app_dirs();
+    app_dirs.extend(extra_dirs.iter().map(|dir| expand_path(dir)));
 
-        for dir in common_dirs {
-            if dir.exists() {
-                for entry in WalkDir::new(&dir)
-                    .max_depth(4)
-                    .into_iter()
-                    .filter_map(|e| e.ok())
-                {
-                    let path = entry.path();
-                    if path.is_file() && !is_app_file(&path.to_path_buf()) {
-                        if let (Ok(metadata), Some(name)) = (path.metadata(), path.file_name().and_then(|n| n.to_str())) {
-                            let modified = metadata
-                                .modified()
-                                .unwrap_or(SystemTime::UNIX_EPOCH)
-                                .duration_since(UNIX_EPOCH)
-                                .unwrap_or_default()
-                                .as_secs();
-
-                            files.push(FileItem {
-                                name: name.to_string(),
-                                path: path.to_string_lossy().to_string(),
-                                file_type: get_file_extension(&path.to_path_buf()),
-                                size: metadata.len(),
-                                modified,
-                                is_app: false,
-                            });
-                        }
+    for app_dir in app_dirs {
+        if app_dir.exists() {
+            for entry in WalkDir::new(&app_dir)
+                .max_depth(3)
+                .into_iter()
+                .filter_map(|e| e.ok())
+            {
+                let path = entry.path();
+                if is_app_file(path) {
+                    if let (Ok(metadata), Some(name)) = (path.metadata(), path.file_name().and_then(|n| n.to_str())) {
+                        apps.push(file_item_from_metadata(path, name, &metadata, true));
                     }
                 }
             }
         }
     }
 
+    apps
+}
+
+// Walk depth for user folders without a max_depth of their own
+const DEFAULT_INDEX_DEPTH: usize = 4;
+
+// `allowlist` holds lowercase extensions without the dot; empty means index everything
+fn index_dir(
+    dir: &Path,
+    max_depth: Option<usize>,
+    allowlist: &[String],
+    job: &Job,
+    files: &mut Vec<FileItem>,
+) {
+    if !dir.exists() {
+        return;
+    }
+    // An allowlisted index stays small, so unless told otherwise it walks the whole tree
+    let default_depth = if allowlist.is_empty() { DEFAULT_INDEX_DEPTH } else { usize::MAX };
+    let max_depth = max_depth.unwrap_or(default_depth);
+    for entry in WalkDir::new(dir)
+        .max_depth(max_depth)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        if job.is_cancelled() {
+            return;
+        }
+        let path = entry.path();
+        let wanted = if allowlist.is_empty() {
+            // Folders are indexed too so they can be opened from the launcher
+            (path.is_file() || path.is_dir()) && !is_app_file(path)
+        } else {
+            path.is_file() && allowlist.contains(&get_file_extension(path))
+        };
+        if wanted {
+            if let (Ok(metadata), Some(name)) = (path.metadata(), path.file_name().and_then(|n| n.to_str())) {
+                files.push(file_item_from_metadata(path, name, &metadata, false));
+            }
+        }
+    }
+}
+
+// Default user folders plus every configured source, read-only ones included, in walk order,
+// each with its max_depth (None = the default for the walk)
+fn user_index_roots(sources: &[IndexSource]) -> Vec<(PathBuf, Option<usize>)> {
+    let mut roots = Vec::new();
+    
+    // Get user home directory
+    if let Some(home_dir) = dirs::home_dir() {
+        roots.extend(
+            ["Documents", "Downloads", "Desktop", "Pictures"]
+                .into_iter()
+                .map(|dir| (home_dir.join(dir), None)),
+        );
+    }
+
+    for source in sources {
+        let path = source.root();
+        match roots.iter_mut().find(|(root, _)| *root == path) {
+            Some(root) => root.1 = source.max_depth,
+            None => roots.push((path, source.max_depth)),
+        }
+    }
+    roots
+}
+
+// Progress of an interrupted file index: roots already walked and what they produced
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexCheckpoint {
+    pub started_at: u64,
+    pub completed_roots: Vec<String>,
+    pub files: Vec<FileItem>,
+}
+
+fn get_index_checkpoint_path(app_handle: &tauri::AppHandle) -> PathBuf {
+    app_handle
+        .path()
+        .app_data_dir()
+        .expect("Failed to get app data dir")
+        .join("file_index_checkpoint.json")
+}
+
+fn load_index_checkpoint(path: &Path) -> Option<IndexCheckpoint> {
+    let json = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+// Walk every user root, checkpointing after each one so an interrupted run can pick up
+// where it stopped. With `resume`, roots finished by the previous run are skipped
+fn index_user_files(
+    sources: &[IndexSource],
+    allowlist: &[String],
+    checkpoint_path: &Path,
+    resume: bool,
+    job: &Job,
+) -> Result<Vec<FileItem>, String> {
+    let allowlist: Vec<String> = allowlist
+        .iter()
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect();
+
+    let mut checkpoint = resume
+        .then(|| load_index_checkpoint(checkpoint_path))
+        .flatten()
+        .unwrap_or_else(|| IndexCheckpoint {
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            ..Default::default()
+        });
+    if !checkpoint.completed_roots.is_empty() {
+        info!("📊 Resuming index, {} roots already done", checkpoint.completed_roots.len());
+    }
+
+    let roots = user_index_roots(sources);
+    for (done, (root, max_depth)) in roots.iter().enumerate() {
+        let key = root.to_string_lossy().to_string();
+        if checkpoint.completed_roots.contains(&key) {
+            continue;
+        }
+        job.progress(done, roots.len(), &key);
+        index_dir(root, *max_depth, &allowlist, job, &mut checkpoint.files);
+        // The checkpoint on disk still covers the roots finished so far, so this can be resumed
+        if job.is_cancelled() {
+            return Err(JOB_CANCELLED.to_string());
+        }
+        checkpoint.completed_roots.push(key);
+        let saved = serde_json::to_string(&checkpoint)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(checkpoint_path, json).map_err(|e| e.to_string()));
+        if let Err(e) = saved {
+            warn!("⚠️  Failed to write index checkpoint: {}", e);
+        }
+    }
+
+    // Finished, so the next refresh starts fresh
+    let _ = fs::remove_file(checkpoint_path);
+    Ok(checkpoint.files)
+}
+
+fn read_only_roots(sources: &[IndexSource]) -> Vec<PathBuf> {
+    sources
+        .iter()
+        .filter(|source| source.read_only)
+        .map(IndexSource::root)
+        .collect()
+}
+
+fn is_under_any(path: &str, roots: &[PathBuf]) -> bool {
+    let path = Path::new(path);
+    roots.iter().any(|root| path.starts_with(root))
+}
+
+// Seconds between a stored Unix timestamp and now; future timestamps count as 0
+fn age_secs(timestamp: u64) -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .saturating_sub(timestamp)
+}
+
+fn with_clipboard_age(mut items: Vec<ClipboardItem>, include_age: Option<bool>) -> Vec<ClipboardItem> {
+    if include_age.unwrap_or(false) {
+        for item in &mut items {
+            item.age_secs = Some(age_secs(item.created_at));
+        }
+    }
+    items
+}
+
+fn with_file_age(mut files: Vec<FileItem>, include_age: Option<bool>) -> Vec<FileItem> {
+    if include_age.unwrap_or(false) {
+        for file in &mut files {
+            file.age_secs = Some(age_secs(file.modified));
+        }
+    }
     files
 }
 
 #[tauri::command]
 fn get_clipboard_history(
     state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    include_age: Option<bool>,
+    source: Option<String>,
+    sort: Option<String>,
+) -> Result<Vec<ClipboardItem>, String> {
+    // "recent" keeps the stored order, "important" blends rating with use (see importance_score)
+    let by_importance = match sort.as_deref() {
+        None | Some("recent") => false,
+        Some("important") => true,
+        Some(other) => return Err(format!("Unknown sort '{}', expected recent or important", other)),
+    };
+    if let Some(source) = source.as_deref() {
+        if !CLIPBOARD_SOURCES.contains(&source) {
+            return Err(format!(
+                "Unknown clipboard source '{}', expected one of {}",
+                source,
+                CLIPBOARD_SOURCES.join(", ")
+            ));
+        }
+    }
+    let db = state.lock().map_err(|e| e.to_string())?;
+    let mut items = db.get_items();
+    if let Some(source) = source.as_deref() {
+        items.retain(|item| item.source == source);
+    }
+    if by_importance {
+        // Pins keep leading in their curated order; the sort is stable so ties stay newest first
+        items.sort_by_key(|item| {
+            let score = if item.pinned { 0 } else { importance_score(item) };
+            (!item.pinned, std::cmp::Reverse(score))
+        });
+    }
+    Ok(with_clipboard_age(items, include_age))
+}
+
+// Each star counts as this many uses, so a rating outweighs casual re-copies
+const RATING_WEIGHT: u64 = 10;
+
+fn importance_score(item: &ClipboardItem) -> u64 {
+    item.rating as u64 * RATING_WEIGHT + item.access_count as u64
+}
+
+// clipboard-update payload: the captured item plus the id of the stored item now on the clipboard,
+// which differs from the item's own id when the copy was merged into an existing entry
+fn clipboard_update_payload(item: &ClipboardItem, current_item_id: &str) -> serde_json::Value {
+    let mut payload = serde_json::to_value(item).unwrap_or_default();
+    if let Some(payload) = payload.as_object_mut() {
+        payload.insert("currentItemId".to_string(), serde_json::json!(current_item_id));
+    }
+    payload
+}
+
+// The history entry matching what's on the system clipboard, or None if nothing stored matches
+#[tauri::command]
+fn get_current_clipboard_item(
+    app_handle: tauri::AppHandle,
+    db: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    monitor: tauri::State<Arc<Mutex<ClipboardMonitorState>>>,
+) -> Result<Option<ClipboardItem>, String> {
+    let db = db.lock().map_err(|e| e.to_string())?;
+    let tracked = monitor.lock().map_err(|e| e.to_string())?.current_item_id.clone();
+    let by_id = |id: &str| db.items.iter().find(|i| i.id == id);
+
+    // The monitor lags by a poll and stops while paused, so text is checked against the live clipboard.
+    // Images aren't re-encoded here; the monitor's last capture is used for them
+    match app_handle.clipboard().read_text() {
+        Ok(text) if !text.is_empty() => {
+            let hash = content_hash(&text);
+            Ok(tracked
+                .as_deref()
+                .and_then(by_id)
+                .filter(|item| item.content_hash == hash)
+                .or_else(|| db.items.iter().find(|i| i.content_hash == hash))
+                .cloned())
+        }
+        _ => Ok(tracked
+            .as_deref()
+            .and_then(by_id)
+            .filter(|item| item.content_type.starts_with("image"))
+            .cloned()),
+    }
+}
+
+// Push the whole history to the frontend, e.g. after a reload dropped some clipboard-update events
+#[tauri::command]
+fn resync_clipboard(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let items = state.lock().map_err(|e| e.to_string())?.get_items();
+    app_handle.emit("clipboard-sync", items).map_err(|e| e.to_string())
+}
+
+// Largest result set search_archive returns; the archive can hold far more than the history
+const MAX_ARCHIVE_RESULTS: usize = 500;
+
+// Search items aged out to the archive, newest first
+#[tauri::command]
+fn search_archive(
+    app_handle: tauri::AppHandle,
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<ClipboardItem>, String> {
+    use std::io::BufRead;
+
+    let fold_diacritics = settings.lock().map_err(|e| e.to_string())?.fold_diacritics;
+    let path = get_archive_path(&app_handle);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let query = fold_for_search(query.trim(), fold_diacritics);
+    let reader = std::io::BufReader::new(fs::File::open(&path).map_err(|e| e.to_string())?);
+    let mut results: Vec<ClipboardItem> = reader
+        .lines()
+        .map_while(Result::ok)
+        // A line cut short by a crash mid-append only loses that one item
+        .filter_map(|line| serde_json::from_str::<ClipboardItem>(&line).ok())
+        .filter(|item| {
+            !item.content_type.starts_with("image/")
+                && fold_for_search(&item.content, fold_diacritics).contains(&query)
+        })
+        .collect();
+    results.sort_by_key(|item| std::cmp::Reverse(item.created_at));
+    results.truncate(limit.unwrap_or(50).min(MAX_ARCHIVE_RESULTS));
+    Ok(results)
+}
+
+#[tauri::command]
+fn search_clipboard(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    query: Option<String>,
+    content_type: Option<String>,
+    since: Option<u64>,
+    until: Option<u64>,
+    include_age: Option<bool>,
 ) -> Result<Vec<ClipboardItem>, String> {
     let db = state.lock().map_err(|e| e.to_string())?;
-    Ok(db.get_items())
+    let fold_diacritics = settings.lock().map_err(|e| e.to_string())?.fold_diacritics;
+    let items = db.search(query.as_deref(), content_type.as_deref(), since, until, fold_diacritics);
+    Ok(with_clipboard_age(items, include_age))
 }
 
 #[tauri::command]
@@ -344,232 +1512,2838 @@ fn update_clipboard_access(
     let mut db = state.lock().map_err(|e| e.to_string())?;
     db.update_access(&id);
     
-    let db_path = get_db_path(&app_handle);
-    save_db(&db, &db_path)?;
+    // Browsing history bumps stats constantly; let the saver batch the writes
+    mark_clipboard_dirty(&app_handle);
     
     Ok(())
 }
 
 #[tauri::command]
-fn delete_clipboard_item(
+fn pin_clipboard_item(
     state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
     app_handle: tauri::AppHandle,
     id: String,
+    pinned: bool,
 ) -> Result<(), String> {
     let mut db = state.lock().map_err(|e| e.to_string())?;
-    db.delete_item(&id);
+    if !db.set_pinned(&id, pinned) {
+        return Err(format!("Clipboard item not found: {}", id));
+    }
     
-    let db_path = get_db_path(&app_handle);
-    save_db(&db, &db_path)?;
+    persist_clipboard_db(&app_handle, &db)?;
     
     Ok(())
 }
 
+const MAX_CLIPBOARD_RATING: u8 = 5;
+
+// One-shot cleanup of duplicates the live dedup in add_item missed or never considered
 #[tauri::command]
-fn clear_clipboard_history(
+fn dedup_clipboard_history(
     state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
     app_handle: tauri::AppHandle,
-) -> Result<(), String> {
+    normalize: bool,
+) -> Result<usize, String> {
     let mut db = state.lock().map_err(|e| e.to_string())?;
-    db.clear_all();
-    
-    let db_path = get_db_path(&app_handle);
-    save_db(&db, &db_path)?;
-    
-    Ok(())
+    let removed = db.dedup(normalize);
+    if removed > 0 {
+        persist_clipboard_db(&app_handle, &db)?;
+        info!("🧹 Removed {} duplicate clipboard items", removed);
+    }
+    Ok(removed)
 }
 
 #[tauri::command]
-fn paste_clipboard_item(
+fn rate_clipboard_item(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
     app_handle: tauri::AppHandle,
-    content: String,
+    id: String,
+    rating: u8,
 ) -> Result<(), String> {
-    use enigo::{Enigo, Key, Keyboard, Settings};
-    
-    // Set clipboard content
-    app_handle.clipboard().write_text(content.clone())
-        .map_err(|e| e.to_string())?;
-    
-    // Small delay to ensure clipboard is set
-    std::thread::sleep(std::time::Duration::from_millis(50));
-    
-    // Simulate Ctrl+V (or Cmd+V on macOS)
-    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| e.to_string())?;
-    
-    #[cfg(target_os = "macos")]
-    {
-        let _ = enigo.key(Key::Meta, enigo::Direction::Press);
-        let _ = enigo.key(Key::Unicode('v'), enigo::Direction::Click);
-        let _ = enigo.key(Key::Meta, enigo::Direction::Release);
-    }
-    
-    #[cfg(not(target_os = "macos"))]
-    {
-        let _ = enigo.key(Key::Control, enigo::Direction::Press);
-        let _ = enigo.key(Key::Unicode('v'), enigo::Direction::Click);
-        let _ = enigo.key(Key::Control, enigo::Direction::Release);
+    if rating > MAX_CLIPBOARD_RATING {
+        return Err(format!("Rating must be between 0 and {}", MAX_CLIPBOARD_RATING));
     }
-    
-    Ok(())
-}
+    let mut db = state.lock().map_err(|e| e.to_string())?;
+    let item = db
+        .items
+        .iter_mut()
+        .find(|item| item.id == id)
+        .ok_or_else(|| format!("Clipboard item not found: {}", id))?;
+    item.rating = rating;
 
-#[tauri::command]
-fn search_files(
-    state: tauri::State<Arc<Mutex<FileSearchDatabase>>>,
-    query: String,
-) -> Result<Vec<FileItem>, String> {
-    let db = state.lock().map_err(|e| e.to_string())?;
-    Ok(db.search_files(&query))
-}
+    persist_clipboard_db(&app_handle, &db)?;
 
-#[tauri::command]
-fn get_applications(
-    state: tauri::State<Arc<Mutex<FileSearchDatabase>>>,
-) -> Result<Vec<FileItem>, String> {
-    let db = state.lock().map_err(|e| e.to_string())?;
-    Ok(db.get_apps())
+    Ok(())
 }
 
 #[tauri::command]
-fn get_recent_files(
-    state: tauri::State<Arc<Mutex<FileSearchDatabase>>>,
-) -> Result<Vec<FileItem>, String> {
-    let db = state.lock().map_err(|e| e.to_string())?;
-    Ok(db.get_recent_files())
+fn reorder_pinned_items(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    app_handle: tauri::AppHandle,
+    ordered_ids: Vec<String>,
+) -> Result<(), String> {
+    let mut db = state.lock().map_err(|e| e.to_string())?;
+    db.reorder_pinned(&ordered_ids)?;
+    
+    persist_clipboard_db(&app_handle, &db)?;
+    
+    Ok(())
 }
 
 #[tauri::command]
-fn open_file(
-    _app_handle: tauri::AppHandle,
-    path: String,
+fn delete_clipboard_item(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    app_handle: tauri::AppHandle,
+    id: String,
 ) -> Result<(), String> {
-    use std::process::Command;
-    
-    #[cfg(target_os = "macos")]
-    {
-        Command::new("open")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| e.to_string())?;
-    }
-    
-    #[cfg(target_os = "windows")]
-    {
-        Command::new("cmd")
-            .args(["/C", "start", "", &path])
-            .spawn()
-            .map_err(|e| e.to_string())?;
-    }
+    let mut db = state.lock().map_err(|e| e.to_string())?;
+    db.delete_item(&id);
     
-    #[cfg(target_os = "linux")]
-    {
-        Command::new("xdg-open")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| e.to_string())?;
-    }
+    persist_clipboard_db(&app_handle, &db)?;
     
     Ok(())
 }
 
 #[tauri::command]
-fn refresh_file_index(
-    state: tauri::State<Arc<Mutex<FileSearchDatabase>>>,
+fn clear_clipboard_history(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
     app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
     let mut db = state.lock().map_err(|e| e.to_string())?;
+    db.clear_all();
     
-    // Clear existing data
-    db.files.clear();
-    db.apps.clear();
-    
-    // Index applications
-    let apps = index_applications();
-    for app in apps {
-        db.add_file(app);
-    }
-    
-    // Index user files
-    let files = index_user_files();
-    for file in files {
-        db.add_file(file);
-    }
-    
-    // Update timestamp
-    db.last_indexed = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    
-    // Save to file
-    let db_path = get_file_search_db_path(&app_handle);
-    save_file_db(&db, &db_path)?;
+    persist_clipboard_db(&app_handle, &db)?;
     
     Ok(())
 }
 
+// Write one ClipboardItem per line so large histories never become one giant string
 #[tauri::command]
-fn hide_window(app: tauri::AppHandle) {
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.hide();
+fn export_clipboard_ndjson(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    path: String,
+) -> Result<usize, String> {
+    use std::io::Write;
+
+    let db = state.lock().map_err(|e| e.to_string())?;
+    let file = fs::File::create(expand_path(&path)).map_err(|e| e.to_string())?;
+    let mut writer = std::io::BufWriter::new(file);
+
+    for item in &db.items {
+        serde_json::to_writer(&mut writer, item).map_err(|e| e.to_string())?;
+        writer.write_all(b"\n").map_err(|e| e.to_string())?;
     }
+    writer.flush().map_err(|e| e.to_string())?;
+
+    Ok(db.items.len())
 }
 
-fn start_clipboard_monitor(app_handle: tauri::AppHandle, db: Arc<Mutex<ClipboardDatabase>>) {
+// Write just the chosen items, in history order, as a JSON array for sharing a curated set
+#[tauri::command]
+fn export_clipboard_items(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    ids: Vec<String>,
+    path: String,
+) -> Result<usize, String> {
+    if ids.is_empty() {
+        return Err("No clipboard items selected".to_string());
+    }
+
+    let db = state.lock().map_err(|e| e.to_string())?;
+    let missing: Vec<&str> = ids
+        .iter()
+        .filter(|id| !db.items.iter().any(|i| i.id == **id))
+        .map(|id| id.as_str())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Clipboard items not found: {}", missing.join(", ")));
+    }
+
+    let selected: Vec<&ClipboardItem> = db.items.iter().filter(|i| ids.contains(&i.id)).collect();
+    let json = serde_json::to_string_pretty(&selected).map_err(|e| e.to_string())?;
+    fs::write(expand_path(&path), json).map_err(|e| e.to_string())?;
+
+    Ok(selected.len())
+}
+
+// Read an NDJSON export line by line, merging items behind the current history.
+// Exports are newest-first, so appending keeps their original order
+#[tauri::command]
+fn import_clipboard_ndjson(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> Result<usize, String> {
+    use std::io::BufRead;
+
+    let file = fs::File::open(expand_path(&path)).map_err(|e| e.to_string())?;
+    let reader = std::io::BufReader::new(file);
+
+    let mut db = state.lock().map_err(|e| e.to_string())?;
+    let mut imported = 0;
+    for (line_num, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let item: ClipboardItem = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid item on line {}: {}", line_num + 1, e))?;
+        if db.append_item(item) {
+            imported += 1;
+        }
+    }
+
+    persist_clipboard_db(&app_handle, &db)?;
+
+    Ok(imported)
+}
+
+#[tauri::command]
+fn set_clipboard_incognito(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    monitor: tauri::State<Arc<Mutex<ClipboardMonitorState>>>,
+    app_handle: tauri::AppHandle,
+    enabled: bool,
+) -> Result<(), String> {
+    let dropped_ids = {
+        let mut monitor = monitor.lock().map_err(|e| e.to_string())?;
+        if monitor.incognito == enabled {
+            return Ok(());
+        }
+        monitor.incognito = enabled;
+        std::mem::take(&mut monitor.incognito_ids)
+    };
+
+    if !enabled {
+        // Leaving incognito: forget everything captured during it, then persist again
+        let mut db = state.lock().map_err(|e| e.to_string())?;
+        for id in &dropped_ids {
+            db.delete_item(id);
+        }
+        persist_clipboard_db(&app_handle, &db)?;
+    }
+
+    Ok(())
+}
+
+// Tauri command to suspend or resume clipboard capture
+#[tauri::command]
+fn set_clipboard_paused(
+    app_handle: tauri::AppHandle,
+    db: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    monitor: tauri::State<Arc<Mutex<ClipboardMonitorState>>>,
+    paused: bool,
+) -> Result<(), String> {
+    let db = db.lock().map_err(|e| e.to_string())?;
+    let mut monitor = monitor.lock().map_err(|e| e.to_string())?;
+    apply_clipboard_paused(&app_handle, &db, &mut monitor, paused);
+    Ok(())
+}
+
+// Shared by set_clipboard_paused and stop_all_activity; returns whether the state changed
+fn apply_clipboard_paused(
+    app_handle: &tauri::AppHandle,
+    db: &ClipboardDatabase,
+    monitor: &mut ClipboardMonitorState,
+    paused: bool,
+) -> bool {
+    if monitor.paused == paused {
+        return false;
+    }
+    monitor.paused = paused;
+
+    if !paused {
+        // Copies made while paused were deliberately not captured, so whatever is on the
+        // clipboard now becomes the monitor's baseline. Read here rather than on the monitor's
+        // next tick, so a copy made right after resuming is still picked up
+        let change_count = clipboard_change_count();
+        let text = app_handle.clipboard().read_text().unwrap_or_default();
+        let image_hash = if text.is_empty() {
+            app_handle.clipboard().read_image().ok().map(|image| rgba_hash(image.rgba()))
+        } else {
+            None
+        };
+        let hash = content_hash(&text);
+        monitor.current_item_id = (!text.is_empty())
+            .then(|| db.items.iter().find(|i| i.content_hash == hash).map(|i| i.id.clone()))
+            .flatten();
+        monitor.resume_baseline = Some(ClipboardBaseline { text, image_hash, change_count });
+    }
+    info!("📋 Clipboard monitor {}", if paused { "paused" } else { "resumed" });
+    true
+}
+
+// Tauri command limiting clipboard-update events to some content types; storage is unaffected
+#[tauri::command]
+fn set_event_filter(
+    monitor: tauri::State<Arc<Mutex<ClipboardMonitorState>>>,
+    content_types: Vec<String>,
+) -> Result<(), String> {
+    let mut monitor = monitor.lock().map_err(|e| e.to_string())?;
+    monitor.event_filter = content_types
+        .into_iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    Ok(())
+}
+
+#[tauri::command]
+fn clipboard_monitor_status(
+    monitor: tauri::State<Arc<Mutex<ClipboardMonitorState>>>,
+) -> Result<serde_json::Value, String> {
+    let monitor = monitor.lock().map_err(|e| e.to_string())?;
+    Ok(serde_json::json!({
+        "running": true,
+        "mode": clipboard_detection_mode(),
+        "paused": monitor.paused,
+        "eventFilter": monitor.event_filter,
+        "incognito": monitor.incognito,
+        "incognitoItems": monitor.incognito_ids.len(),
+    }))
+}
+
+// Upper bounds for the title lookup so a slow or huge page can't stall the action
+const TITLE_FETCH_TIMEOUT_SECS: u64 = 3;
+const TITLE_FETCH_MAX_BYTES: usize = 256 * 1024;
+
+// Pull the <title> text out of an HTML document
+fn extract_html_title(html: &str) -> Option<String> {
+    // ASCII-only lowercasing keeps byte offsets valid for slicing the original
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+
+    let title = html[start..end]
+        .replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+async fn fetch_page_title(url: &str) -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(TITLE_FETCH_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Title fetch returned {}", response.status()));
+    }
+
+    // The title lives in <head>, so the start of the page is enough
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= TITLE_FETCH_MAX_BYTES {
+            break;
+        }
+    }
+
+    extract_html_title(&String::from_utf8_lossy(&body))
+        .ok_or_else(|| "Page has no title".to_string())
+}
+
+// Copy a URL item as [title](url), using the given title or the page's own
+#[tauri::command]
+async fn copy_as_markdown_link(
+    state: tauri::State<'_, Arc<Mutex<ClipboardDatabase>>>,
+    app_handle: tauri::AppHandle,
+    id: String,
+    title: Option<String>,
+) -> Result<String, String> {
+    let url = {
+        let db = state.lock().map_err(|e| e.to_string())?;
+        let item = db
+            .items
+            .iter()
+            .find(|item| item.id == id)
+            .ok_or_else(|| format!("Clipboard item not found: {}", id))?;
+        if item.content_type != "url" {
+            return Err("Clipboard item is not a URL".to_string());
+        }
+        item.content.trim().to_string()
+    };
+
+    let title = match title.filter(|t| !t.trim().is_empty()) {
+        Some(title) => Some(title.trim().to_string()),
+        None => match fetch_page_title(&url).await {
+            Ok(title) => Some(title),
+            Err(e) => {
+                warn!("⚠️  Could not fetch title for {}: {}", url, e);
+                None
+            }
+        },
+    };
+
+    // Without a title the plain URL is still the most useful thing to copy
+    let text = match title {
+        Some(title) => format!("[{}]({})", title.replace('[', "\\[").replace(']', "\\]"), url),
+        None => url,
+    };
+
+    expect_clipboard_source(&app_handle, &text, CLIPBOARD_SOURCE_COPY);
+    app_handle.clipboard().write_text(text.clone())
+        .map_err(|e| e.to_string())?;
+    Ok(text)
+}
+
+// Long enough to click into another app, short enough that a typo isn't a stuck paste
+const MAX_FOCUS_DELAY_MS: u64 = 10_000;
+const PASTE_COUNTDOWN_TICK_MS: u64 = 250;
+
+#[tauri::command]
+fn paste_clipboard_item(
+    app_handle: tauri::AppHandle,
+    content: String,
+    content_type: Option<String>,
+    focus_delay_ms: Option<u64>,
+    prompt_permission: Option<bool>,
+) -> Result<(), String> {
+    // Fail before touching the clipboard or hiding the window if the keystroke would be dropped
+    ensure_accessibility_permission(prompt_permission.unwrap_or(false))?;
+
+    // Image items hold base64 data, which has to go back on the clipboard as an image
+    let image_type = content_type.filter(|t| t.starts_with("image/"));
+    let paste = move |app_handle: &tauri::AppHandle, content: String| {
+        if let Some(image_type) = image_type.as_deref() {
+            paste_image(app_handle, &content, image_type)
+        } else {
+            paste_text(app_handle, content)
+        }
+    };
+
+    let delay_ms = match focus_delay_ms {
+        Some(ms) if ms > 0 => ms.min(MAX_FOCUS_DELAY_MS),
+        _ => return paste(&app_handle, content),
+    };
+
+    // Get the launcher out of the way so the user can click into the target app
+    hide_main_window(&app_handle);
+
+    std::thread::spawn(move || {
+        let mut remaining = delay_ms;
+        while remaining > 0 {
+            let _ = app_handle.emit("paste-countdown", remaining);
+            let tick = remaining.min(PASTE_COUNTDOWN_TICK_MS);
+            std::thread::sleep(std::time::Duration::from_millis(tick));
+            remaining -= tick;
+        }
+        let _ = app_handle.emit("paste-countdown", 0u64);
+
+        if let Err(e) = paste(&app_handle, content) {
+            warn!("⚠️  Delayed paste failed: {}", e);
+        }
+    });
+
+    Ok(())
+}
+
+// Prefix the frontend matches on to show the "grant Accessibility access" flow
+const ACCESSIBILITY_PERMISSION_ERROR: &str = "accessibility_permission_required";
+
+// Keystroke simulation is silently dropped on macOS until the app is trusted for Accessibility
+#[cfg(target_os = "macos")]
+fn accessibility_trusted(prompt: bool) -> bool {
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    struct CFDictionaryCallBacks {
+        _private: [u8; 0],
+    }
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn AXIsProcessTrusted() -> bool;
+        fn AXIsProcessTrustedWithOptions(options: *const c_void) -> bool;
+        static kAXTrustedCheckOptionPrompt: *const c_void;
+    }
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        static kCFBooleanTrue: *const c_void;
+        static kCFTypeDictionaryKeyCallBacks: CFDictionaryCallBacks;
+        static kCFTypeDictionaryValueCallBacks: CFDictionaryCallBacks;
+        fn CFDictionaryCreate(
+            allocator: *const c_void,
+            keys: *const *const c_void,
+            values: *const *const c_void,
+            count: isize,
+            key_callbacks: *const CFDictionaryCallBacks,
+            value_callbacks: *const CFDictionaryCallBacks,
+        ) -> *const c_void;
+        fn CFRelease(object: *const c_void);
+    }
+
+    unsafe {
+        if !prompt {
+            return AXIsProcessTrusted();
+        }
+        // Passing the prompt option makes macOS show its "allow Accessibility" dialog when untrusted
+        let keys = [kAXTrustedCheckOptionPrompt];
+        let values = [kCFBooleanTrue];
+        let options = CFDictionaryCreate(
+            std::ptr::null(),
+            keys.as_ptr(),
+            values.as_ptr(),
+            1,
+            &kCFTypeDictionaryKeyCallBacks,
+            &kCFTypeDictionaryValueCallBacks,
+        );
+        if options.is_null() {
+            return AXIsProcessTrusted();
+        }
+        let trusted = AXIsProcessTrustedWithOptions(options);
+        CFRelease(options);
+        trusted
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn accessibility_trusted(_prompt: bool) -> bool {
+    true
+}
+
+fn ensure_accessibility_permission(prompt: bool) -> Result<(), String> {
+    if accessibility_trusted(prompt) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}: Grant PathFinder Accessibility access in System Settings > Privacy & Security > Accessibility, then try again",
+            ACCESSIBILITY_PERMISSION_ERROR
+        ))
+    }
+}
+
+#[tauri::command]
+fn check_accessibility_permission(prompt: Option<bool>) -> bool {
+    accessibility_trusted(prompt.unwrap_or(false))
+}
+
+// Put text on the clipboard and paste it into the focused app
+fn paste_text(app_handle: &tauri::AppHandle, content: String) -> Result<(), String> {
+    // Set clipboard content
+    expect_clipboard_source(app_handle, &content, CLIPBOARD_SOURCE_PASTE);
+    app_handle.clipboard().write_text(content)
+        .map_err(|e| e.to_string())?;
+    
+    // Small delay to ensure clipboard is set
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    
+    simulate_paste()
+}
+
+const PASTE_TRANSFORMS: &[&str] = &["upper", "lower", "trim", "url_decode", "base64_decode", "json_pretty"];
+
+// Decode %XX escapes; anything malformed or non-UTF-8 is an error rather than a guess
+fn percent_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text
+                .get(i + 1..i + 3)
+                .ok_or_else(|| format!("Malformed escape at position {}", i))?;
+            // from_str_radix would also accept a sign, as in "%+1"
+            let byte = hex
+                .bytes()
+                .all(|b| b.is_ascii_hexdigit())
+                .then(|| u8::from_str_radix(hex, 16).ok())
+                .flatten()
+                .ok_or_else(|| format!("Invalid escape %{} at position {}", hex, i))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| "Decoded text is not valid UTF-8".to_string())
+}
+
+fn apply_paste_transform(content: &str, transform: &str) -> Result<String, String> {
+    use base64::Engine;
+
+    match transform {
+        "upper" => Ok(content.to_uppercase()),
+        "lower" => Ok(content.to_lowercase()),
+        "trim" => Ok(content.trim().to_string()),
+        "url_decode" => percent_decode(content),
+        "base64_decode" => {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(content.trim())
+                .map_err(|e| format!("Invalid base64: {}", e))?;
+            String::from_utf8(bytes).map_err(|_| "Decoded data is not text".to_string())
+        }
+        "json_pretty" => {
+            let value: serde_json::Value =
+                serde_json::from_str(content).map_err(|e| format!("Invalid JSON: {}", e))?;
+            serde_json::to_string_pretty(&value).map_err(|e| e.to_string())
+        }
+        _ => Err(format!(
+            "Unknown transform '{}', expected one of: {}",
+            transform,
+            PASTE_TRANSFORMS.join(", ")
+        )),
+    }
+}
+
+// Paste a text item after running it through one of PASTE_TRANSFORMS
+#[tauri::command]
+fn paste_transformed(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    app_handle: tauri::AppHandle,
+    id: String,
+    transform: String,
+) -> Result<(), String> {
+    let content = {
+        let db = state.lock().map_err(|e| e.to_string())?;
+        let item = db
+            .items
+            .iter()
+            .find(|item| item.id == id)
+            .ok_or_else(|| format!("Clipboard item not found: {}", id))?;
+        if item.content_type.starts_with("image/") {
+            return Err("Transforms only apply to text items".to_string());
+        }
+        item.content.clone()
+    };
+
+    let transformed = apply_paste_transform(&content, &transform)?;
+    paste_text(&app_handle, transformed)
+}
+
+// Image crate format for a stored item's MIME type
+fn image_format_for(content_type: &str) -> Option<image::ImageFormat> {
+    match content_type {
+        "image/png" => Some(image::ImageFormat::Png),
+        "image/jpeg" => Some(image::ImageFormat::Jpeg),
+        "image/webp" => Some(image::ImageFormat::WebP),
+        "image/bmp" => Some(image::ImageFormat::Bmp),
+        "image/gif" => Some(image::ImageFormat::Gif),
+        "image/tiff" => Some(image::ImageFormat::Tiff),
+        _ => None,
+    }
+}
+
+// Decode a stored image item back onto the clipboard. The OS clipboard gets
+// raw RGBA whatever the stored format; animated GIFs paste their first frame
+fn write_clipboard_image(app_handle: &tauri::AppHandle, content: &str, content_type: &str) -> Result<(), String> {
+    use base64::Engine;
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(content.trim())
+        .map_err(|e| format!("Stored image is corrupt: {}", e))?;
+    // Items imported from elsewhere may be mislabelled, so fall back to sniffing the data
+    let decoded = image_format_for(content_type)
+        .and_then(|format| image::load_from_memory_with_format(&bytes, format).ok())
+        .map(Ok)
+        .unwrap_or_else(|| image::load_from_memory(&bytes))
+        .map_err(|e| format!("Failed to decode image: {}", e))?
+        .into_rgba8();
+    let (width, height) = decoded.dimensions();
+
+    let monitor = app_handle.state::<Arc<Mutex<ClipboardMonitorState>>>();
+    if let Ok(mut monitor) = monitor.lock() {
+        monitor.pasted_image_hash = Some(rgba_hash(decoded.as_raw()));
+    }
+
+    let image = tauri::image::Image::new_owned(decoded.into_raw(), width, height);
+    app_handle.clipboard().write_image(&image)
+        .map_err(|e| e.to_string())
+}
+
+// Put a stored image item back on the clipboard and paste it
+fn paste_image(app_handle: &tauri::AppHandle, content: &str, content_type: &str) -> Result<(), String> {
+    write_clipboard_image(app_handle, content, content_type)?;
+
+    // Small delay to ensure clipboard is set
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    simulate_paste()
+}
+
+// Tauri command for debugging paste: sends only the keystroke, leaving the clipboard alone
+#[tauri::command]
+fn test_paste() -> Result<(), String> {
+    simulate_paste()
+}
+
+// Simulate Ctrl+V (or Cmd+V on macOS)
+fn simulate_paste() -> Result<(), String> {
+    use enigo::{Enigo, Key, Keyboard, Settings};
+    
+    ensure_accessibility_permission(false)?;
+
+    let mut enigo = Enigo::new(&Settings::default()).map_err(|e| {
+        if cfg!(target_os = "macos") {
+            format!(
+                "Failed to initialize keyboard simulation ({}). Grant PathFinder Accessibility access in System Settings > Privacy & Security",
+                e
+            )
+        } else {
+            format!("Failed to initialize keyboard simulation: {}", e)
+        }
+    })?;
+    
+    #[cfg(target_os = "macos")]
+    let modifier = Key::Meta;
+    #[cfg(not(target_os = "macos"))]
+    let modifier = Key::Control;
+    
+    // Always release the modifier, even if the V press failed, so it isn't left stuck down
+    let pressed = enigo
+        .key(modifier, enigo::Direction::Press)
+        .and_then(|_| enigo.key(Key::Unicode('v'), enigo::Direction::Click));
+    let released = enigo.key(modifier, enigo::Direction::Release);
+    pressed
+        .and(released)
+        .map_err(|e| format!("Failed to simulate paste keystroke: {}", e))
+}
+
+// A "search here" scope only makes sense inside a tree the index actually covers
+fn resolve_search_root(root: &str, sources: &[IndexSource]) -> Result<PathBuf, String> {
+    let root = fs::canonicalize(expand_path(root)).map_err(|e| format!("Invalid search root {}: {}", root, e))?;
+    let indexed = user_index_roots(sources)
+        .into_iter()
+        .map(|(dir, _)| fs::canonicalize(&dir).unwrap_or(dir))
+        .any(|dir| root.starts_with(&dir));
+    if !indexed {
+        return Err(format!("{} is not under any indexed directory", root.display()));
+    }
+    Ok(root)
+}
+
+#[tauri::command]
+fn search_files(
+    state: tauri::State<Arc<Mutex<FileSearchDatabase>>>,
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    metrics: tauri::State<Arc<Mutex<PerfMetrics>>>,
+    query: String,
+    include_age: Option<bool>,
+    root: Option<String>,
+) -> Result<Vec<FileItem>, String> {
+    let (hidden_apps, fold_diacritics, index_sources) = {
+        let settings = settings.lock().map_err(|e| e.to_string())?;
+        (settings.hidden_apps.clone(), settings.fold_diacritics, settings.index_sources.clone())
+    };
+    let root = root
+        .map(|root| resolve_search_root(&root, &index_sources))
+        .transpose()?;
+    let db = state.lock().map_err(|e| e.to_string())?;
+    let started = std::time::Instant::now();
+    let results = db.search_files(&query, &hidden_apps, fold_diacritics, root.as_deref());
+    let elapsed = started.elapsed();
+
+    if let Ok(mut metrics) = metrics.lock() {
+        metrics.last_search_query = query;
+        metrics.last_search_duration_us = elapsed.as_micros() as u64;
+        metrics.last_search_results = results.len();
+        metrics.search_count += 1;
+    }
+    Ok(with_file_age(results, include_age))
+}
+
+// Keeps a benchmark from holding the index lock for too long
+const MAX_BENCHMARK_ITERATIONS: usize = 1000;
+
+// Diagnostic: time the same file search repeatedly, reporting latency in microseconds
+#[tauri::command]
+fn benchmark_search(
+    state: tauri::State<Arc<Mutex<FileSearchDatabase>>>,
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    query: String,
+    iterations: usize,
+) -> Result<serde_json::Value, String> {
+    let iterations = iterations.clamp(1, MAX_BENCHMARK_ITERATIONS);
+    let (hidden_apps, fold_diacritics) = {
+        let settings = settings.lock().map_err(|e| e.to_string())?;
+        (settings.hidden_apps.clone(), settings.fold_diacritics)
+    };
+    let db = state.lock().map_err(|e| e.to_string())?;
+
+    let mut timings = Vec::with_capacity(iterations);
+    let mut results = 0;
+    for _ in 0..iterations {
+        let started = std::time::Instant::now();
+        results = db.search_files(&query, &hidden_apps, fold_diacritics, None).len();
+        timings.push(started.elapsed().as_micros() as u64);
+    }
+
+    let total: u64 = timings.iter().sum();
+    Ok(serde_json::json!({
+        "iterations": iterations,
+        "minUs": timings.iter().min().copied().unwrap_or(0),
+        "maxUs": timings.iter().max().copied().unwrap_or(0),
+        "avgUs": total / iterations as u64,
+        "results": results,
+        "indexSize": db.files.len() + db.apps.len(),
+    }))
+}
+
+// Drill into a folder result: the same search, limited to entries inside that folder
+#[tauri::command]
+fn search_in_folder(
+    state: tauri::State<Arc<Mutex<FileSearchDatabase>>>,
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    folder: String,
+    query: String,
+    include_age: Option<bool>,
+) -> Result<Vec<FileItem>, String> {
+    let folder = expand_path(&folder);
+    if !folder.is_dir() {
+        return Err(format!("Not a folder: {}", folder.display()));
+    }
+    let (hidden_apps, fold_diacritics) = {
+        let settings = settings.lock().map_err(|e| e.to_string())?;
+        (settings.hidden_apps.clone(), settings.fold_diacritics)
+    };
+    let db = state.lock().map_err(|e| e.to_string())?;
+    let results = db.search_files(&query, &hidden_apps, fold_diacritics, Some(&folder));
+    Ok(with_file_age(results, include_age))
+}
+
+#[tauri::command]
+fn get_applications(
+    state: tauri::State<Arc<Mutex<FileSearchDatabase>>>,
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    include_age: Option<bool>,
+) -> Result<Vec<FileItem>, String> {
+    let hidden_apps = settings.lock().map_err(|e| e.to_string())?.hidden_apps.clone();
+    let db = state.lock().map_err(|e| e.to_string())?;
+    Ok(with_file_age(db.get_apps(&hidden_apps), include_age))
+}
+
+fn sync_apps_cache(app_handle: &tauri::AppHandle, db: &FileSearchDatabase) {
+    if let Some(cache) = app_handle.try_state::<Arc<Mutex<AppsCache>>>() {
+        if let Ok(mut cache) = cache.lock() {
+            cache.apps = db.apps.clone();
+        }
+    }
+}
+
+// Lower is better: exact name, prefix, start of a word, then anywhere in the name
+fn app_match_rank(name: &str, query: &str) -> Option<u8> {
+    if name == query {
+        Some(0)
+    } else if name.starts_with(query) {
+        Some(1)
+    } else if name
+        .match_indices(query)
+        .any(|(i, _)| name[..i].ends_with([' ', '-', '_', '.']))
+    {
+        Some(2)
+    } else if name.contains(query) {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+// Tauri command for the launcher's hot path: ranked app search from the apps cache
+#[tauri::command]
+fn search_apps(
+    cache: tauri::State<Arc<Mutex<AppsCache>>>,
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    query: String,
+) -> Result<Vec<FileItem>, String> {
+    let (hidden_apps, fold_diacritics) = {
+        let settings = settings.lock().map_err(|e| e.to_string())?;
+        (settings.hidden_apps.clone(), settings.fold_diacritics)
+    };
+    let query = fold_for_search(query.trim(), fold_diacritics);
+    let cache = cache.lock().map_err(|e| e.to_string())?;
+
+    let mut ranked: Vec<(u8, String, &FileItem)> = cache
+        .apps
+        .iter()
+        .filter(|app| !hidden_apps.contains(&app.path))
+        .filter_map(|app| {
+            let name = fold_for_search(&app.name, fold_diacritics);
+            app_match_rank(&name, &query).map(|rank| (rank, name, app))
+        })
+        .collect();
+    // Shorter names first within a rank: "Code" before "Code Helper"
+    ranked.sort_by(|a, b| (a.0, a.1.len(), &a.1).cmp(&(b.0, b.1.len(), &b.1)));
+
+    Ok(ranked.into_iter().take(20).map(|(_, _, app)| app.clone()).collect())
+}
+
+// Hidden apps stay indexed, so unhiding doesn't need a reindex
+fn set_app_hidden(
+    settings: &Mutex<AppSettings>,
+    app_handle: &tauri::AppHandle,
+    path: String,
+    hidden: bool,
+) -> Result<(), String> {
+    let mut settings = settings.lock().map_err(|e| e.to_string())?;
+    let mut updated = settings.clone();
+    updated.hidden_apps.retain(|p| *p != path);
+    if hidden {
+        updated.hidden_apps.push(path);
+    }
+    save_settings(&updated, &get_settings_path(app_handle))?;
+    *settings = updated;
+    Ok(())
+}
+
+#[tauri::command]
+fn hide_app(
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> Result<(), String> {
+    set_app_hidden(&settings, &app_handle, path, true)
+}
+
+#[tauri::command]
+fn unhide_app(
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> Result<(), String> {
+    set_app_hidden(&settings, &app_handle, path, false)
+}
+
+#[tauri::command]
+fn get_recent_files(
+    state: tauri::State<Arc<Mutex<FileSearchDatabase>>>,
+    include_age: Option<bool>,
+) -> Result<Vec<FileItem>, String> {
+    let db = state.lock().map_err(|e| e.to_string())?;
+    Ok(with_file_age(db.get_recent_files(), include_age))
+}
+
+#[tauri::command]
+fn open_file(
+    app_handle: tauri::AppHandle,
+    path: String,
+    hide_after: Option<bool>,
+    background: Option<bool>,
+) -> Result<(), String> {
+    // Background opens keep the launcher up so a batch can be opened while typing
+    let background = background.unwrap_or(false);
+    open_path(&path, background)?;
+    
+    if background {
+        // Only macOS can open without activating; elsewhere take focus back once the app is up
+        if !cfg!(target_os = "macos") {
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(BACKGROUND_OPEN_REFOCUS_MS));
+                if let Some(window) = app_handle.get_webview_window("main") {
+                    let _ = window.set_focus();
+                }
+            });
+        }
+        return Ok(());
+    }
+    
+    // Dismiss the launcher so it doesn't linger over the opened app
+    if hide_after.unwrap_or(true) {
+        hide_main_window(&app_handle);
+    }
+    
+    Ok(())
+}
+
+// Upper bound on max_bytes so a preview can never pull a whole large file into memory
+const MAX_FILE_PREVIEW_BYTES: usize = 1024 * 1024;
+
+// Read-only preview data for the launcher's preview pane: text head, image size, or metadata
+#[tauri::command]
+fn get_file_preview(path: String, max_bytes: usize) -> Result<serde_json::Value, String> {
+    use std::io::Read;
+
+    let path = PathBuf::from(path);
+    let metadata = fs::metadata(&path).map_err(|e| e.to_string())?;
+    let mut preview = serde_json::json!({
+        "size": if metadata.is_dir() { 0 } else { metadata.len() },
+        "modified": system_time_secs(metadata.modified()),
+        "readonly": metadata.permissions().readonly(),
+    });
+
+    if metadata.is_dir() {
+        preview["kind"] = "folder".into();
+        preview["entries"] = fs::read_dir(&path).map(|entries| entries.count()).unwrap_or(0).into();
+        return Ok(preview);
+    }
+
+    // Only the header is read, so this stays cheap for large images
+    if let Ok(format) = image::ImageFormat::from_path(&path) {
+        if let Ok((width, height)) = image::image_dimensions(&path) {
+            preview["kind"] = "image".into();
+            preview["format"] = format.extensions_str().first().copied().unwrap_or("").into();
+            preview["width"] = width.into();
+            preview["height"] = height.into();
+            return Ok(preview);
+        }
+    }
+
+    let limit = max_bytes.min(MAX_FILE_PREVIEW_BYTES);
+    let mut head = Vec::with_capacity(limit);
+    fs::File::open(&path)
+        .and_then(|file| file.take(limit as u64).read_to_end(&mut head))
+        .map_err(|e| e.to_string())?;
+
+    // A NUL byte is a reliable sign the file isn't text
+    if head.contains(&0) {
+        preview["kind"] = "binary".into();
+    } else {
+        preview["kind"] = "text".into();
+        preview["text"] = String::from_utf8_lossy(&head).into_owned().into();
+        preview["truncated"] = ((head.len() as u64) < metadata.len()).into();
+    }
+    Ok(preview)
+}
+
+// Cap for copy_file_contents; history items this large already make the list sluggish
+const MAX_COPY_FILE_BYTES: usize = 10 * 1024 * 1024;
+
+// Put a text file's contents (not its path) on the clipboard, recorded as a pathfinder-copy
+#[tauri::command]
+fn copy_file_contents(
+    app_handle: tauri::AppHandle,
+    path: String,
+    max_bytes: usize,
+) -> Result<serde_json::Value, String> {
+    use std::io::Read;
+
+    let path = PathBuf::from(path);
+    let metadata = fs::metadata(&path).map_err(|e| e.to_string())?;
+    if !metadata.is_file() {
+        return Err(format!("Not a file: {}", path.display()));
+    }
+
+    let limit = max_bytes.clamp(1, MAX_COPY_FILE_BYTES);
+    let mut bytes = Vec::with_capacity(limit.min(metadata.len() as usize));
+    fs::File::open(&path)
+        .and_then(|file| file.take(limit as u64).read_to_end(&mut bytes))
+        .map_err(|e| e.to_string())?;
+    // Same NUL-byte check as get_file_preview
+    if bytes.contains(&0) {
+        return Err(format!("{} looks like a binary file", path.display()));
+    }
+    let content = String::from_utf8_lossy(&bytes).into_owned();
+    let truncated = (bytes.len() as u64) < metadata.len();
+
+    // The monitor records it under pathfinder-copy, like the other in-app writes
+    let size = content.len();
+    expect_clipboard_source(&app_handle, &content, CLIPBOARD_SOURCE_COPY);
+    app_handle.clipboard().write_text(content).map_err(|e| e.to_string())?;
+
+    Ok(serde_json::json!({
+        "size": size,
+        "truncated": truncated,
+    }))
+}
+
+// Split a template into argv entries on whitespace; double quotes group words with spaces
+fn split_command_template(template: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_arg = false;
+    for c in template.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_arg = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_arg {
+                    args.push(std::mem::take(&mut current));
+                    has_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_arg = true;
+            }
+        }
+    }
+    if in_quotes {
+        return Err("Unterminated quote in command".to_string());
+    }
+    if has_arg {
+        args.push(current);
+    }
+    if args.is_empty() {
+        return Err("Command is empty".to_string());
+    }
+    Ok(args)
+}
+
+// Run a configured quick action on a result. Placeholders are substituted inside each
+// argument, so a path with spaces or shell characters stays a single argument
+#[tauri::command]
+fn run_quick_action(
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    action_name: String,
+    path: String,
+) -> Result<(), String> {
+    let action = settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .quick_actions
+        .iter()
+        .find(|action| action.name == action_name)
+        .cloned()
+        .ok_or_else(|| format!("Quick action not found: {}", action_name))?;
+
+    // Same checks as open_file: an existing path that can't be read as an option
+    let target = validate_launch_path(&path)?;
+    let dir = if target.is_dir() {
+        target.clone()
+    } else {
+        target.parent().map(Path::to_path_buf).unwrap_or_else(|| target.clone())
+    };
+    let name = target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let dir_str = dir.to_string_lossy().to_string();
+
+    let args: Vec<String> = split_command_template(&action.command_template)?
+        .into_iter()
+        .map(|arg| arg.replace("{path}", &path).replace("{dir}", &dir_str).replace("{name}", &name))
+        .collect();
+
+    let mut child = std::process::Command::new(&args[0])
+        .args(&args[1..])
+        .current_dir(&dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", args[0], e))?;
+    info!("⚡ Ran quick action '{}' on {}", action.name, path);
+
+    // Reap it in the background so finished actions don't linger as zombies
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+// Scratch files for open_clipboard_item_in_editor; wiped on every launch
+fn get_editor_temp_dir() -> PathBuf {
+    std::env::temp_dir().join("pathfinder_clipboard")
+}
+
+// Pick an extension from the content itself so the editor highlights it; classify_content only
+// tells urls from text
+fn editor_extension_for(content: &str) -> &'static str {
+    let trimmed = content.trim();
+    let is_json = (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(trimmed).is_ok();
+    let is_markdown = trimmed.lines().any(|line| {
+        let line = line.trim_start();
+        line.starts_with("```") || (line.starts_with('#') && line.trim_start_matches('#').starts_with(' '))
+    });
+    if is_json {
+        "json"
+    } else if trimmed.starts_with('<') && trimmed.ends_with('>') {
+        "html"
+    } else if is_markdown {
+        "md"
+    } else {
+        "txt"
+    }
+}
+
+// Write an item to a temp file and open it with the default editor for its type
+#[tauri::command]
+fn open_clipboard_item_in_editor(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    app_handle: tauri::AppHandle,
+    id: String,
+) -> Result<(), String> {
+    let content = {
+        let db = state.lock().map_err(|e| e.to_string())?;
+        let item = db
+            .items
+            .iter()
+            .find(|item| item.id == id)
+            .ok_or_else(|| format!("Clipboard item not found: {}", id))?;
+        item.content.clone()
+    };
+
+    let dir = get_editor_temp_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    // Item ids may contain characters that aren't safe in file names
+    let safe_id: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    let path = dir.join(format!("clip-{}.{}", safe_id, editor_extension_for(&content)));
+    fs::write(&path, content).map_err(|e| e.to_string())?;
+
+    open_file(app_handle, path.to_string_lossy().to_string(), None, None)
+}
+
+// Read clipboard text as a local path: a file:// URL or a raw (optionally quoted, ~ or
+// env-var) path. Only existing paths count
+fn clipboard_text_as_path(text: &str) -> Option<PathBuf> {
+    let text = text.trim();
+    if text.is_empty() || text.contains(['\n', '\r']) {
+        return None;
+    }
+
+    let path = match text.strip_prefix("file://") {
+        Some(rest) => {
+            let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+            let decoded = percent_decode(rest).ok()?;
+            // file:///C:/Users/... keeps a slash in front of the drive letter
+            let bytes = decoded.as_bytes();
+            if cfg!(target_os = "windows") && bytes.len() >= 3 && bytes[0] == b'/' && bytes[2] == b':' {
+                PathBuf::from(&decoded[1..])
+            } else {
+                PathBuf::from(decoded)
+            }
+        }
+        None => expand_path(text.trim_matches(|c| c == '"' || c == '\'')),
+    };
+    path.exists().then_some(path)
+}
+
+// Open a history item whose content is a path to an existing file or folder
+#[tauri::command]
+fn open_clipboard_item_as_path(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    app_handle: tauri::AppHandle,
+    id: String,
+) -> Result<(), String> {
+    let content = {
+        let db = state.lock().map_err(|e| e.to_string())?;
+        let item = db
+            .items
+            .iter()
+            .find(|item| item.id == id)
+            .ok_or_else(|| format!("Clipboard item not found: {}", id))?;
+        if item.content_type.starts_with("image/") {
+            return Err("Clipboard item is an image, not a path".to_string());
+        }
+        item.content.clone()
+    };
+
+    let path = clipboard_text_as_path(&content)
+        .ok_or_else(|| "Clipboard item is not a path to an existing file".to_string())?;
+    open_file(app_handle, path.to_string_lossy().to_string(), None, None)
+}
+
+// Write the newest image item to a temp file and open it in the default viewer
+#[tauri::command]
+fn open_latest_image(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    app_handle: tauri::AppHandle,
+) -> Result<(), String> {
+    use base64::Engine;
+
+    let (id, content, content_type) = {
+        let db = state.lock().map_err(|e| e.to_string())?;
+        let item = db
+            .items
+            .iter()
+            .filter(|item| item.content_type.starts_with("image/"))
+            .max_by_key(|item| item.created_at)
+            .ok_or_else(|| "No image in clipboard history".to_string())?;
+        (item.id.clone(), item.content.clone(), item.content_type.clone())
+    };
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(content)
+        .map_err(|e| format!("Stored image is corrupt: {}", e))?;
+
+    let dir = get_editor_temp_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    // Only the most recent one is ever needed, so drop earlier exports
+    if let Ok(entries) = fs::read_dir(&dir) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            if entry.file_name().to_string_lossy().starts_with("image-") {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    let safe_id: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    let extension = match content_type.as_str() {
+        "image/jpeg" => "jpg",
+        "image/webp" => "webp",
+        "image/bmp" => "bmp",
+        "image/gif" => "gif",
+        "image/tiff" => "tiff",
+        _ => "png",
+    };
+    let path = dir.join(format!("image-{}.{}", safe_id, extension));
+    fs::write(&path, bytes).map_err(|e| e.to_string())?;
+
+    open_file(app_handle, path.to_string_lossy().to_string(), None, None)
+}
+
+// Reject paths that could be interpreted as something other than a single existing file.
+// Everything we launch is passed as one argv entry and never through a shell
+fn validate_launch_path(path: &str) -> Result<PathBuf, String> {
+    if path.trim().is_empty() {
+        return Err("Path is empty".to_string());
+    }
+    if path.chars().any(|c| c == '\0' || c == '\n' || c == '\r') {
+        return Err("Path contains control characters".to_string());
+    }
+    // A leading dash would be parsed as an option by open/xdg-open
+    if path.starts_with('-') {
+        return Err(format!("Refusing to open path starting with '-': {}", path));
+    }
+    // Quotes can't appear in Windows file names, so one here means an injection attempt
+    if cfg!(target_os = "windows") && path.contains('"') {
+        return Err(format!("Path contains a quote character: {}", path));
+    }
+
+    let path_buf = PathBuf::from(path);
+    if !path_buf.exists() {
+        return Err(format!("Path does not exist: {}", path));
+    }
+    Ok(path_buf)
+}
+
+// How long a background open waits for the launched app before refocusing the launcher
+const BACKGROUND_OPEN_REFOCUS_MS: u64 = 400;
+
+// Open a file, folder or app with the OS default handler. `background` uses `open -g`
+// on macOS so the app isn't brought to the front; other platforms ignore it here
+fn open_path(path: &str, background: bool) -> Result<(), String> {
+    let path = validate_launch_path(path)?;
+
+    #[cfg(target_os = "windows")]
+    {
+        let _ = background;
+        // ShellExecute, not explorer: explorer splits its argument on commas and exits
+        // nonzero even when it opened the file. No cmd.exe involved either way
+        tauri_plugin_opener::open_path(&path, None::<&str>).map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        open_command(&path, background)
+            .spawn()
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+// The path is always a single argv entry of a launcher that doesn't go through a shell
+#[cfg(not(target_os = "windows"))]
+fn open_command(path: &Path, background: bool) -> std::process::Command {
+    use std::process::Command;
+
+    #[cfg(target_os = "macos")]
+    {
+        let mut command = Command::new("open");
+        if background {
+            command.arg("-g");
+        }
+        command.arg(path);
+        command
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = background;
+        let mut command = Command::new("xdg-open");
+        command.arg(path);
+        command
+    }
+}
+
+// Device strings go straight into FFmpeg's -i, so only allow what device specs look like
+fn validate_device_spec(device: &str) -> Result<(), String> {
+    if device.len() > 256 {
+        return Err("Device string is too long".to_string());
+    }
+    if device.chars().any(|c| c.is_control() || c == '"' || c == '\'' || c == '`' || c == '$') {
+        return Err(format!("Device string contains invalid characters: {}", device));
+    }
+    if device.starts_with('-') {
+        return Err(format!("Device string can't start with '-': {}", device));
+    }
+    Ok(())
+}
+
+// Swap in a freshly walked app list; apps change rarely so this is usually skipped
+fn reindex_apps(db: &mut FileSearchDatabase, indexed: Vec<FileItem>) -> usize {
+    db.apps.clear();
+    db.app_keys.clear();
+    for app in indexed {
+        db.add_file(app);
+    }
+    db.apps_indexed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    db.last_indexed = db.apps_indexed;
+    db.apps.len()
+}
+
+// Swap in a freshly walked user file list, leaving apps untouched. Entries under read-only
+// sources are kept and new ones merged in, so a shared folder is never cleared
+fn reindex_files(db: &mut FileSearchDatabase, settings: &AppSettings, indexed: Vec<FileItem>) -> usize {
+    let read_only = read_only_roots(&settings.index_sources);
+    db.files.retain(|item| is_under_any(&item.path, &read_only));
+    let mut known: std::collections::HashSet<String> =
+        db.files.iter().map(|item| item.path.clone()).collect();
+    for file in indexed {
+        // Sources can overlap each other or the default folders
+        if known.insert(file.path.clone()) {
+            db.add_file(file);
+        }
+    }
+    db.files_indexed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    db.last_indexed = db.files_indexed;
+    db.files.len()
+}
+
+// ========== Jobs (long-running operations) ==========
+
+// Error returned by work that stopped because cancel_job was called
+const JOB_CANCELLED: &str = "Job cancelled";
+
+// What start_job can run: kind -> (apps, files) for refresh_index
+const INDEX_JOB_KINDS: &[(&str, bool, bool)] = &[
+    ("reindex", true, true),
+    ("reindex_apps", true, false),
+    ("reindex_files", false, true),
+];
+
+#[derive(Debug)]
+struct JobEntry {
+    kind: &'static str,
+    started_at: u64,
+    cancel: Arc<std::sync::atomic::AtomicBool>,
+    progress: (usize, usize),
+}
+
+// Running jobs by id; a job removes itself when its Job handle is dropped
+#[derive(Debug, Default)]
+pub struct JobRegistry {
+    jobs: std::collections::HashMap<String, JobEntry>,
+}
+
+// Handle held by the code doing the work. Progress and completion are emitted as
+// job-progress / job-done events keyed by id
+struct Job {
+    id: String,
+    kind: &'static str,
+    app_handle: tauri::AppHandle,
+    cancel: Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl Job {
+    fn start(app_handle: &tauri::AppHandle, kind: &'static str) -> Self {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let cancel = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        if let Ok(mut registry) = app_handle.state::<Arc<Mutex<JobRegistry>>>().lock() {
+            registry.jobs.insert(id.clone(), JobEntry {
+                kind,
+                started_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+                cancel: cancel.clone(),
+                progress: (0, 0),
+            });
+        }
+        Self {
+            id,
+            kind,
+            app_handle: app_handle.clone(),
+            cancel,
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    fn progress(&self, done: usize, total: usize, message: &str) {
+        if let Ok(mut registry) = self.app_handle.state::<Arc<Mutex<JobRegistry>>>().lock() {
+            if let Some(entry) = registry.jobs.get_mut(&self.id) {
+                entry.progress = (done, total);
+            }
+        }
+        let _ = self.app_handle.emit(
+            "job-progress",
+            serde_json::json!({
+                "id": self.id,
+                "kind": self.kind,
+                "done": done,
+                "total": total,
+                "message": message,
+            }),
+        );
+    }
+
+    fn finish(&self, result: &Result<(), String>) {
+        let _ = self.app_handle.emit(
+            "job-done",
+            serde_json::json!({
+                "id": self.id,
+                "kind": self.kind,
+                "success": result.is_ok(),
+                "cancelled": self.is_cancelled(),
+                "error": result.as_ref().err(),
+            }),
+        );
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        if let Ok(mut registry) = self.app_handle.state::<Arc<Mutex<JobRegistry>>>().lock() {
+            registry.jobs.remove(&self.id);
+        }
+    }
+}
+
+// Claim the reindex slot before registering the job, so a second reindex is rejected
+// instead of showing up as another job writing the same checkpoint
+fn start_index_job(app_handle: &tauri::AppHandle, kind: &'static str) -> Result<(Job, ReindexGuard), String> {
+    let guard = ReindexGuard::claim()?;
+    Ok((Job::start(app_handle, kind), guard))
+}
+
+// Run a reindex as a job and report how it ended
+fn run_index_job(
+    app_handle: &tauri::AppHandle,
+    job: &Job,
+    apps: bool,
+    files: bool,
+    resume: bool,
+) -> Result<(), String> {
+    let state = app_handle.state::<Arc<Mutex<FileSearchDatabase>>>();
+    let settings = app_handle.state::<Arc<Mutex<AppSettings>>>();
+    let result = refresh_index(&state, &settings, app_handle, apps, files, resume, job);
+    job.finish(&result);
+    result
+}
+
+// Tauri command to start a long-running operation in the background, returning its job id
+#[tauri::command]
+fn start_job(app_handle: tauri::AppHandle, kind: String, resume: Option<bool>) -> Result<String, String> {
+    let &(kind, apps, files) = INDEX_JOB_KINDS
+        .iter()
+        .find(|(name, _, _)| *name == kind)
+        .ok_or_else(|| {
+            let kinds: Vec<&str> = INDEX_JOB_KINDS.iter().map(|(name, _, _)| *name).collect();
+            format!("Unknown job kind '{}', expected one of {}", kind, kinds.join(", "))
+        })?;
+
+    let (job, guard) = start_index_job(&app_handle, kind)?;
+    let id = job.id.clone();
+    std::thread::spawn(move || {
+        let _guard = guard;
+        if let Err(e) = run_index_job(&app_handle, &job, apps, files, resume.unwrap_or(false)) {
+            warn!("⚠️  Job {} ({}) failed: {}", job.id, job.kind, e);
+        }
+    });
+    Ok(id)
+}
+
+// Ask a running job to stop; it finishes with a job-done event marked cancelled
+#[tauri::command]
+fn cancel_job(jobs: tauri::State<Arc<Mutex<JobRegistry>>>, id: String) -> Result<(), String> {
+    let registry = jobs.lock().map_err(|e| e.to_string())?;
+    let entry = registry.jobs.get(&id).ok_or_else(|| format!("No running job: {}", id))?;
+    entry.cancel.store(true, std::sync::atomic::Ordering::SeqCst);
+    Ok(())
+}
+
+#[tauri::command]
+fn list_jobs(jobs: tauri::State<Arc<Mutex<JobRegistry>>>) -> Result<Vec<serde_json::Value>, String> {
+    let registry = jobs.lock().map_err(|e| e.to_string())?;
+    Ok(registry
+        .jobs
+        .iter()
+        .map(|(id, entry)| {
+            serde_json::json!({
+                "id": id,
+                "kind": entry.kind,
+                "startedAt": entry.started_at,
+                "done": entry.progress.0,
+                "total": entry.progress.1,
+                "cancelling": entry.cancel.load(std::sync::atomic::Ordering::SeqCst),
+            })
+        })
+        .collect())
+}
+
+// Set while a reindex job runs; only one reindex may walk the disk and write the checkpoint at a time
+static REINDEXING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+const REINDEX_RUNNING: &str = "A reindex is already running";
+
+// Clears REINDEXING on every return path, errors included
+struct ReindexGuard;
+
+impl ReindexGuard {
+    fn claim() -> Result<Self, String> {
+        REINDEXING
+            .compare_exchange(false, true, std::sync::atomic::Ordering::SeqCst, std::sync::atomic::Ordering::SeqCst)
+            .map(|_| Self)
+            .map_err(|_| REINDEX_RUNNING.to_string())
+    }
+}
+
+impl Drop for ReindexGuard {
+    fn drop(&mut self) {
+        REINDEXING.store(false, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+// Shared body of the refresh commands: rebuild the chosen collections, record timings, save.
+// Callers hold the ReindexGuard from start_index_job
+fn refresh_index(
+    state: &Mutex<FileSearchDatabase>,
+    settings: &Mutex<AppSettings>,
+    app_handle: &tauri::AppHandle,
+    apps: bool,
+    files: bool,
+    resume: bool,
+    job: &Job,
+) -> Result<(), String> {
+    let settings = settings.lock().map_err(|e| e.to_string())?.clone();
+    let started = std::time::Instant::now();
+
+    // Walk without the index lock so searches keep working, and so a cancelled run leaves
+    // the existing index alone
+    let indexed_apps = apps.then(|| {
+        job.progress(0, 0, "Indexing applications");
+        index_applications(&settings.app_dirs)
+    });
+    let indexed_files = if files {
+        Some(index_user_files(
+            &settings.index_sources,
+            &settings.index_extension_allowlist,
+            &get_index_checkpoint_path(app_handle),
+            resume,
+            job,
+        )?)
+    } else {
+        None
+    };
+
+    let mut db = state.lock().map_err(|e| e.to_string())?;
+    let mut entries = 0;
+    if let Some(indexed) = indexed_apps {
+        entries += reindex_apps(&mut db, indexed);
+    }
+    if let Some(indexed) = indexed_files {
+        entries += reindex_files(&mut db, &settings, indexed);
+    }
+    if apps {
+        sync_apps_cache(app_handle, &db);
+    }
+
+    record_index_metrics(app_handle, &db, entries, started.elapsed());
+
+    let db_path = get_file_search_db_path(app_handle);
+    save_file_db(&db, &db_path).inspect_err(|e| report_persistence_error(app_handle, "file index", e))
+}
+
+// Shorter intervals would have a large index walking the disk almost continuously
+const MIN_AUTO_REINDEX_INTERVAL_SECS: u64 = 60;
+// How often the auto reindexer re-reads its interval, so settings changes apply without a restart
+const AUTO_REINDEX_POLL_SECS: u64 = 15;
+
+// Background rebuild on the auto_reindex_interval_secs cadence; emits auto-reindex-started
+// and auto-reindex-finished so the UI can show it
+fn start_auto_reindexer(app_handle: tauri::AppHandle) {
+    std::thread::spawn(move || {
+        let mut last_run = std::time::Instant::now();
+        loop {
+            std::thread::sleep(std::time::Duration::from_secs(AUTO_REINDEX_POLL_SECS));
+
+            let settings = app_handle.state::<Arc<Mutex<AppSettings>>>();
+            let interval = match settings.lock() {
+                Ok(settings) => settings.auto_reindex_interval_secs,
+                Err(_) => continue,
+            };
+            if interval == 0 || last_run.elapsed().as_secs() < interval {
+                continue;
+            }
+            last_run = std::time::Instant::now();
+
+            let Ok((job, _guard)) = start_index_job(&app_handle, "reindex") else {
+                debug!("Skipping auto reindex, a reindex is already running");
+                continue;
+            };
+
+            info!("📊 Auto reindexing files");
+            let _ = app_handle.emit("auto-reindex-started", ());
+            let result = run_index_job(&app_handle, &job, true, true, false);
+            if let Err(e) = &result {
+                warn!("⚠️  Auto reindex failed: {}", e);
+            }
+            let _ = app_handle.emit(
+                "auto-reindex-finished",
+                serde_json::json!({
+                    "success": result.is_ok(),
+                    "error": result.err(),
+                }),
+            );
+        }
+    });
+}
+
+// `resume` continues an interrupted run from its checkpoint (see get_index_checkpoint).
+// Waits for the rebuild; start_job("reindex") runs the same thing in the background
+#[tauri::command]
+fn refresh_file_index(app_handle: tauri::AppHandle, resume: Option<bool>) -> Result<(), String> {
+    let (job, _guard) = start_index_job(&app_handle, "reindex")?;
+    run_index_job(&app_handle, &job, true, true, resume.unwrap_or(false))
+}
+
+// Tauri command so the UI can offer to resume an index that was interrupted
+#[tauri::command]
+fn get_index_checkpoint(app_handle: tauri::AppHandle) -> Option<serde_json::Value> {
+    let checkpoint = load_index_checkpoint(&get_index_checkpoint_path(&app_handle))?;
+    Some(serde_json::json!({
+        "startedAt": checkpoint.started_at,
+        "completedRoots": checkpoint.completed_roots,
+        "files": checkpoint.files.len(),
+    }))
+}
+
+// Tauri command to rebuild just the applications, without walking user folders
+#[tauri::command]
+fn refresh_apps_only(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let (job, _guard) = start_index_job(&app_handle, "reindex_apps")?;
+    run_index_job(&app_handle, &job, true, false, false)
+}
+
+// Tauri command to rebuild just the user files, skipping Program Files/Applications
+#[tauri::command]
+fn refresh_files_only(app_handle: tauri::AppHandle, resume: Option<bool>) -> Result<(), String> {
+    let (job, _guard) = start_index_job(&app_handle, "reindex_files")?;
+    run_index_job(&app_handle, &job, false, true, resume.unwrap_or(false))
+}
+
+fn record_index_metrics(
+    app_handle: &tauri::AppHandle,
+    db: &FileSearchDatabase,
+    entries: usize,
+    elapsed: std::time::Duration,
+) {
+    let secs = elapsed.as_secs_f64();
+    info!("📊 Indexed {} entries in {:.2}s", entries, secs);
+
+    let metrics = app_handle.state::<Arc<Mutex<PerfMetrics>>>();
+    if let Ok(mut metrics) = metrics.lock() {
+        metrics.last_index_duration_ms = elapsed.as_millis() as u64;
+        metrics.last_index_entries = entries;
+        metrics.index_entries_per_sec = if secs > 0.0 { entries as f64 / secs } else { 0.0 };
+        metrics.last_indexed_at = db.last_indexed;
+    };
+}
+
+#[tauri::command]
+fn get_perf_metrics(
+    metrics: tauri::State<Arc<Mutex<PerfMetrics>>>,
+) -> Result<PerfMetrics, String> {
+    let metrics = metrics.lock().map_err(|e| e.to_string())?;
+    Ok(metrics.clone())
+}
+
+// Drop dead and duplicate index entries, reporting the index size before and after
+#[tauri::command]
+fn compact_file_index(
+    state: tauri::State<Arc<Mutex<FileSearchDatabase>>>,
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    app_handle: tauri::AppHandle,
+) -> Result<serde_json::Value, String> {
+    let read_only = read_only_roots(&settings.lock().map_err(|e| e.to_string())?.index_sources);
+    let mut db = state.lock().map_err(|e| e.to_string())?;
+
+    let before_files = db.files.len();
+    let before_apps = db.apps.len();
+    db.compact(&read_only);
+    sync_apps_cache(&app_handle, &db);
+
+    let db_path = get_file_search_db_path(&app_handle);
+    save_file_db(&db, &db_path)?;
+
+    Ok(serde_json::json!({
+        "before": { "files": before_files, "apps": before_apps },
+        "after": { "files": db.files.len(), "apps": db.apps.len() },
+    }))
+}
+
+// Current (size, modified) of an indexed path, or None if it no longer exists
+fn stat_indexed_path(path: &str) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let size = if metadata.is_dir() { 0 } else { metadata.len() };
+    Some((size, system_time_secs(metadata.modified())))
+}
+
+// Tauri command to reconcile the index with the filesystem without a full reindex
+#[tauri::command]
+async fn verify_file_index(
+    state: tauri::State<'_, Arc<Mutex<FileSearchDatabase>>>,
+    settings: tauri::State<'_, Arc<Mutex<AppSettings>>>,
+    app_handle: tauri::AppHandle,
+) -> Result<serde_json::Value, String> {
+    let read_only = read_only_roots(&settings.lock().map_err(|e| e.to_string())?.index_sources);
+    let state = state.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        // Stat outside the lock so searches aren't blocked while the disk is checked.
+        // Read-only sources are skipped, so their entries stay exactly as indexed
+        let paths: Vec<String> = {
+            let db = state.lock().map_err(|e| e.to_string())?;
+            db.files
+                .iter()
+                .chain(db.apps.iter())
+                .filter(|item| !is_under_any(&item.path, &read_only))
+                .map(|item| item.path.clone())
+                .collect()
+        };
+        let current: HashMap<String, Option<(u64, u64)>> = paths
+            .iter()
+            .map(|path| (path.clone(), stat_indexed_path(path)))
+            .collect();
+
+        let mut guard = state.lock().map_err(|e| e.to_string())?;
+        let db = &mut *guard;
+        let mut updated = 0;
+        let mut removed = 0;
+        for items in [&mut db.files, &mut db.apps] {
+            items.retain_mut(|item| match current.get(&item.path) {
+                Some(None) => {
+                    removed += 1;
+                    false
+                }
+                Some(Some((size, modified))) => {
+                    if item.size != *size || item.modified != *modified {
+                        item.size = *size;
+                        item.modified = *modified;
+                        updated += 1;
+                    }
+                    true
+                }
+                // Indexed after the snapshot was taken
+                None => true,
+            });
+        }
+
+        if updated > 0 || removed > 0 {
+            sync_apps_cache(&app_handle, db);
+            save_file_db(db, &get_file_search_db_path(&app_handle))?;
+        }
+        info!("🔎 Verified {} index entries: {} updated, {} removed", paths.len(), updated, removed);
+
+        Ok(serde_json::json!({
+            "checked": paths.len(),
+            "updated": updated,
+            "removed": removed,
+        }))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// The frontend owns the schema of the session blob (last query, active tab, ...)
+#[tauri::command]
+fn save_session_state(
+    app_handle: tauri::AppHandle,
+    state: serde_json::Value,
+) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&state).map_err(|e| e.to_string())?;
+    fs::write(get_session_state_path(&app_handle), json).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn load_session_state(app_handle: tauri::AppHandle) -> Result<Option<serde_json::Value>, String> {
+    let path = get_session_state_path(&app_handle);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let json = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let state = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    Ok(Some(state))
+}
+
+// The window-shown/window-hidden events let the frontend time focus and animations
+fn hide_main_window(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        if window.hide().is_ok() {
+            let _ = app.emit("window-hidden", ());
+        }
+    }
+}
+
+fn show_main_window(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+        let _ = app.emit("window-shown", ());
+    }
+}
+
+#[tauri::command]
+fn get_settings(
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+) -> Result<AppSettings, String> {
+    let settings = settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.clone())
+}
+
+#[tauri::command]
+fn update_settings(
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    app_handle: tauri::AppHandle,
+    mut new_settings: AppSettings,
+) -> Result<(), String> {
+    if new_settings.auto_reindex_interval_secs != 0
+        && new_settings.auto_reindex_interval_secs < MIN_AUTO_REINDEX_INTERVAL_SECS
+    {
+        return Err(format!(
+            "Auto reindex interval must be 0 (disabled) or at least {} seconds",
+            MIN_AUTO_REINDEX_INTERVAL_SECS
+        ));
+    }
+
+    if !CLIPBOARD_IMAGE_FORMATS.contains(&new_settings.image_format.as_str()) {
+        return Err(format!(
+            "Unknown image format '{}', expected one of {}",
+            new_settings.image_format,
+            CLIPBOARD_IMAGE_FORMATS.join(", ")
+        ));
+    }
+
+    let mut action_names = std::collections::HashSet::new();
+    for action in &new_settings.quick_actions {
+        if action.name.trim().is_empty() || !action_names.insert(action.name.as_str()) {
+            return Err(format!("Quick action names must be unique and non-empty: '{}'", action.name));
+        }
+        split_command_template(&action.command_template)
+            .map_err(|e| format!("Quick action '{}': {}", action.name, e))?;
+    }
+
+    // Reject a bad hotkey before saving it
+    let new_quick_paste = new_settings
+        .quick_paste_shortcut
+        .as_deref()
+        .map(parse_shortcut)
+        .transpose()?;
+
+    let mut settings = settings.lock().map_err(|e| e.to_string())?;
+    let old_quick_paste = settings
+        .quick_paste_shortcut
+        .as_deref()
+        .and_then(|spec| parse_shortcut(spec).ok());
+
+    // Register the quick paste shortcut before saving, so one the OS rejects (e.g. already
+    // taken by another app) never reaches disk to fail again on every launch
+    let shortcut_changed = old_quick_paste.as_ref().map(|s| s.id()) != new_quick_paste.as_ref().map(|s| s.id());
+    let swap_shortcut = |from: Option<Shortcut>, to: Option<Shortcut>| -> Result<(), String> {
+        if let Some(from) = from {
+            let _ = app_handle.global_shortcut().unregister(from);
+        }
+        match to {
+            Some(to) => app_handle
+                .global_shortcut()
+                .register(to)
+                .map_err(|e| format!("Failed to register quick paste shortcut: {}", e)),
+            None => Ok(()),
+        }
+    };
+    if shortcut_changed {
+        if let Err(e) = swap_shortcut(old_quick_paste, new_quick_paste) {
+            let _ = swap_shortcut(None, old_quick_paste);
+            return Err(e);
+        }
+    }
+
+    // The profile only changes through switch_profile, which also swaps the loaded history
+    new_settings.clipboard_profile = settings.clipboard_profile.clone();
+    if let Err(e) = save_settings(&new_settings, &get_settings_path(&app_handle)) {
+        if shortcut_changed {
+            let _ = swap_shortcut(new_quick_paste, old_quick_paste);
+        }
+        return Err(e);
+    }
+    *settings = new_settings;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn list_profiles(app_handle: tauri::AppHandle) -> Result<Vec<String>, String> {
+    let app_data_dir = app_handle.path().app_data_dir().map_err(|e| e.to_string())?;
+
+    let mut profiles: Vec<String> = fs::read_dir(&app_data_dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_prefix("clipboard_history_")
+                .and_then(|rest| rest.strip_suffix(".json"))
+                .filter(|profile| validate_profile_name(profile).is_ok())
+                .map(|profile| profile.to_string())
+        })
+        .collect();
+    profiles.sort();
+    profiles.insert(0, DEFAULT_CLIPBOARD_PROFILE.to_string());
+    Ok(profiles)
+}
+
+#[tauri::command]
+fn create_profile(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    app_handle: tauri::AppHandle,
+    name: String,
+) -> Result<(), String> {
+    validate_profile_name(&name)?;
+    let path = get_profile_db_path(&app_handle, &name);
+    if path.exists() {
+        return Err(format!("Profile '{}' already exists", name));
+    }
+
+    // New profiles inherit the current history limit
+    let max_items = state.lock().map_err(|e| e.to_string())?.max_items;
+    save_db(&ClipboardDatabase::new(max_items), &path)
+}
+
+// Flush the active history and load another profile's in its place
+#[tauri::command]
+fn switch_profile(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    settings: tauri::State<Arc<Mutex<AppSettings>>>,
+    app_handle: tauri::AppHandle,
+    name: String,
+) -> Result<(), String> {
+    validate_profile_name(&name)?;
+    let target_path = get_profile_db_path(&app_handle, &name);
+    if name != DEFAULT_CLIPBOARD_PROFILE && !target_path.exists() {
+        return Err(format!("Profile '{}' does not exist", name));
+    }
+
+    let mut db = state.lock().map_err(|e| e.to_string())?;
+    persist_clipboard_db(&app_handle, &db)?;
+    let target = load_db(&target_path)?;
+
+    {
+        let mut settings = settings.lock().map_err(|e| e.to_string())?;
+        let mut updated = settings.clone();
+        updated.clipboard_profile = name.clone();
+        save_settings(&updated, &get_settings_path(&app_handle))?;
+        *settings = updated;
+    }
+    // The monitor shares this handle, so new copies land in the new profile from here on
+    *db = target;
+
+    info!("📋 Switched clipboard profile to '{}'", name);
+    let _ = app_handle.emit("clipboard-profile-changed", name);
+    Ok(())
+}
+
+#[tauri::command]
+fn hide_window(app: tauri::AppHandle) {
+    hide_main_window(&app);
+}
+
+// Picking a history item: bump its stats, put it on the clipboard and hide the launcher in one call
+#[tauri::command]
+fn copy_and_hide(
+    state: tauri::State<Arc<Mutex<ClipboardDatabase>>>,
+    app_handle: tauri::AppHandle,
+    id: String,
+) -> Result<ClipboardItem, String> {
+    let item = {
+        let mut db = state.lock().map_err(|e| e.to_string())?;
+        db.update_access(&id);
+        db.items
+            .iter()
+            .find(|item| item.id == id)
+            .cloned()
+            .ok_or_else(|| format!("Clipboard item not found: {}", id))?
+    };
+    mark_clipboard_dirty(&app_handle);
+
+    if item.content_type.starts_with("image/") {
+        write_clipboard_image(&app_handle, &item.content, &item.content_type)?;
+    } else {
+        expect_clipboard_source(&app_handle, &item.content, CLIPBOARD_SOURCE_COPY);
+        app_handle.clipboard().write_text(item.content.clone())
+            .map_err(|e| e.to_string())?;
+    }
+
+    hide_main_window(&app_handle);
+    Ok(item)
+}
+
+// Parse a user-entered shortcut string like "Ctrl+Shift+Space" or "Alt+Space"
+fn parse_shortcut(spec: &str) -> Result<Shortcut, String> {
+    let spec = spec.trim();
+    if spec.is_empty() {
+        return Err("Shortcut is empty".to_string());
+    }
+
+    let parts: Vec<&str> = spec.split('+').map(|p| p.trim()).collect();
+    if parts.iter().any(|p| p.is_empty()) {
+        return Err(format!("Shortcut \"{}\" contains an empty key (check for a stray '+')", spec));
+    }
+
+    let mut modifiers = Modifiers::empty();
+    let mut key: Option<&str> = None;
+    for part in &parts {
+        let modifier = match part.to_lowercase().as_str() {
+            "ctrl" | "control" => Some(Modifiers::CONTROL),
+            "shift" => Some(Modifiers::SHIFT),
+            "alt" | "option" => Some(Modifiers::ALT),
+            "super" | "cmd" | "command" | "meta" => Some(Modifiers::SUPER),
+            "cmdorctrl" | "commandorcontrol" => Some(if cfg!(target_os = "macos") {
+                Modifiers::SUPER
+            } else {
+                Modifiers::CONTROL
+            }),
+            _ => None,
+        };
+
+        match modifier {
+            Some(m) => modifiers |= m,
+            None => {
+                if let Some(existing) = key {
+                    return Err(format!(
+                        "Shortcut \"{}\" has more than one key (\"{}\" and \"{}\")",
+                        spec, existing, part
+                    ));
+                }
+                key = Some(part);
+            }
+        }
+    }
+
+    let key = key.ok_or_else(|| format!("Shortcut \"{}\" has no key, only modifiers", spec))?;
+
+    // Let the plugin's own parser resolve the key code so we accept exactly what registration accepts
+    let normalized = if modifiers.is_empty() {
+        key.to_string()
+    } else {
+        let mut names = Vec::new();
+        if modifiers.contains(Modifiers::CONTROL) {
+            names.push("ctrl");
+        }
+        if modifiers.contains(Modifiers::SHIFT) {
+            names.push("shift");
+        }
+        if modifiers.contains(Modifiers::ALT) {
+            names.push("alt");
+        }
+        if modifiers.contains(Modifiers::SUPER) {
+            names.push("super");
+        }
+        format!("{}+{}", names.join("+"), key)
+    };
+
+    normalized
+        .parse::<Shortcut>()
+        .map_err(|e| format!("Invalid key \"{}\" in shortcut \"{}\": {}", key, spec, e))
+}
+
+#[tauri::command]
+fn validate_shortcut(spec: String) -> Result<(), String> {
+    parse_shortcut(&spec).map(|_| ())
+}
+
+// The quick paste shortcut currently configured, if any and valid
+fn configured_quick_paste_shortcut(app: &tauri::AppHandle) -> Option<Shortcut> {
+    let settings = app.state::<Arc<Mutex<AppSettings>>>();
+    let spec = settings.lock().ok()?.quick_paste_shortcut.clone()?;
+    parse_shortcut(&spec).ok()
+}
+
+// Paste the history item at the configured recency index, skipping the OS clipboard
+fn quick_paste(app: &tauri::AppHandle) {
+    let index = match app.state::<Arc<Mutex<AppSettings>>>().lock() {
+        Ok(settings) => settings.quick_paste_index,
+        Err(_) => return,
+    };
+
+    let item = {
+        let db = app.state::<Arc<Mutex<ClipboardDatabase>>>();
+        let Ok(db) = db.lock() else { return };
+        let mut items: Vec<&ClipboardItem> = db.items.iter().collect();
+        items.sort_by_key(|item| std::cmp::Reverse(item.last_accessed));
+        items.get(index).map(|item| (item.content.clone(), item.content_type.clone()))
+    };
+
+    if let Some((content, content_type)) = item {
+        let app = app.clone();
+        // Off the shortcut thread, and after a beat so the hotkey's modifiers are released
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(150));
+            // Image items hold base64 data, which has to go back on the clipboard as an image
+            let result = if content_type.starts_with("image/") {
+                paste_image(&app, &content, &content_type)
+            } else {
+                paste_text(&app, content)
+            };
+            if let Err(e) = result {
+                warn!("⚠️  Quick paste failed: {}", e);
+            }
+        });
+    }
+}
+
+// How the monitor notices new copies: a cheap OS change counter where available, otherwise polling
+fn clipboard_detection_mode() -> &'static str {
+    #[cfg(target_os = "macos")]
+    {
+        "changeCount"
+    }
+    #[cfg(target_os = "windows")]
+    {
+        "sequenceNumber"
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        "polling"
+    }
+}
+
+// NSPasteboard.generalPasteboard.changeCount, via the Objective-C runtime
+#[cfg(target_os = "macos")]
+fn clipboard_change_count() -> Option<u64> {
+    use std::ffi::{c_char, c_void};
+
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {}
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> *mut c_void;
+        fn sel_registerName(name: *const c_char) -> *mut c_void;
+        fn objc_msgSend();
+    }
+
+    unsafe {
+        let class = objc_getClass(c"NSPasteboard".as_ptr());
+        if class.is_null() {
+            return None;
+        }
+        let send_object: unsafe extern "C" fn(*mut c_void, *mut c_void) -> *mut c_void =
+            std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let pasteboard = send_object(class, sel_registerName(c"generalPasteboard".as_ptr()));
+        if pasteboard.is_null() {
+            return None;
+        }
+        let send_integer: unsafe extern "C" fn(*mut c_void, *mut c_void) -> isize =
+            std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        Some(send_integer(pasteboard, sel_registerName(c"changeCount".as_ptr())) as u64)
+    }
+}
+
+// GetClipboardSequenceNumber bumps on every clipboard write
+#[cfg(target_os = "windows")]
+fn clipboard_change_count() -> Option<u64> {
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetClipboardSequenceNumber() -> u32;
+    }
+
+    // Returns 0 when the caller lacks access to the window station
+    match unsafe { GetClipboardSequenceNumber() } {
+        0 => None,
+        sequence => Some(sequence as u64),
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn clipboard_change_count() -> Option<u64> {
+    None
+}
+
+// Screenshots beyond this (raw RGBA) are too big to keep in a JSON history
+const MAX_CLIPBOARD_IMAGE_BYTES: usize = 64 * 1024 * 1024;
+
+const CLIPBOARD_IMAGE_FORMATS: &[&str] = &["png", "jpeg", "webp"];
+
+fn rgba_hash(rgba: &[u8]) -> u64 {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    rgba.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Encode raw RGBA pixels in the configured storage format, returning the bytes and MIME type
+fn encode_clipboard_image(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    format: &str,
+    quality: u8,
+) -> Result<(Vec<u8>, &'static str), String> {
+    use image::ImageEncoder;
+
+    let mut encoded = Vec::new();
+    match format {
+        "jpeg" => {
+            // JPEG has no alpha channel
+            let rgb: Vec<u8> = rgba.chunks_exact(4).flat_map(|px| [px[0], px[1], px[2]]).collect();
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut encoded, quality.clamp(1, 100))
+                .write_image(&rgb, width, height, image::ExtendedColorType::Rgb8)
+                .map_err(|e| e.to_string())?;
+            Ok((encoded, "image/jpeg"))
+        }
+        "webp" => {
+            // The image crate only writes lossless WebP, which is still far smaller than PNG for screenshots
+            image::codecs::webp::WebPEncoder::new_lossless(&mut encoded)
+                .write_image(rgba, width, height, image::ExtendedColorType::Rgba8)
+                .map_err(|e| e.to_string())?;
+            Ok((encoded, "image/webp"))
+        }
+        _ => {
+            image::codecs::png::PngEncoder::new(&mut encoded)
+                .write_image(rgba, width, height, image::ExtendedColorType::Rgba8)
+                .map_err(|e| e.to_string())?;
+            Ok((encoded, "image/png"))
+        }
+    }
+}
+
+// Returns the clipboard image as base64 in the configured format, its MIME type and encoded size,
+// if it changed since last time
+fn capture_clipboard_image(
+    app_handle: &tauri::AppHandle,
+    last_hash: &mut Option<u64>,
+    format: &str,
+    quality: u8,
+) -> Option<(String, String, usize)> {
+    use base64::Engine;
+
+    let image = app_handle.clipboard().read_image().ok()?;
+    let rgba = image.rgba();
+    if rgba.is_empty() || rgba.len() > MAX_CLIPBOARD_IMAGE_BYTES {
+        return None;
+    }
+
+    // Hash the raw pixels so an unchanged image isn't re-encoded every tick
+    let hash = rgba_hash(rgba);
+    if *last_hash == Some(hash) {
+        return None;
+    }
+    *last_hash = Some(hash);
+
+    // A pasted history item decodes to different bytes than it was stored as,
+    // so it would otherwise come back as a near-duplicate entry
+    let monitor = app_handle.state::<Arc<Mutex<ClipboardMonitorState>>>();
+    if monitor.lock().ok()?.pasted_image_hash == Some(hash) {
+        return None;
+    }
+
+    let (encoded, content_type) = match encode_clipboard_image(rgba, image.width(), image.height(), format, quality) {
+        Ok(result) => result,
+        Err(e) => {
+            warn!("⚠️  Failed to encode clipboard image: {}", e);
+            return None;
+        }
+    };
+
+    let size = encoded.len();
+    Some((
+        base64::engine::general_purpose::STANDARD.encode(encoded),
+        content_type.to_string(),
+        size,
+    ))
+}
+
+// Set while the monitor thread is alive, for get_system_status
+static CLIPBOARD_MONITOR_RUNNING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+// Clears CLIPBOARD_MONITOR_RUNNING if the monitor thread ever exits, panics included
+struct ClipboardMonitorGuard;
+
+impl Drop for ClipboardMonitorGuard {
+    fn drop(&mut self) {
+        CLIPBOARD_MONITOR_RUNNING.store(false, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+fn start_clipboard_monitor(
+    app_handle: tauri::AppHandle,
+    db: Arc<Mutex<ClipboardDatabase>>,
+    monitor: Arc<Mutex<ClipboardMonitorState>>,
+) {
+    // Seed with the most recently used stored item so the clipboard content that
+    // survived a restart isn't captured again as a brand new copy
+    let (mut last_content, seed_id) = db
+        .lock()
+        .ok()
+        .and_then(|db| {
+            db.items
+                .iter()
+                .max_by_key(|i| i.last_accessed)
+                .map(|i| (i.content.clone(), Some(i.id.clone())))
+        })
+        .unwrap_or_default();
+    if let Ok(mut monitor) = monitor.lock() {
+        monitor.current_item_id = seed_id;
+    }
+
+    // Start unset so whatever was copied while the app was closed is still checked once
+    let mut last_change_count: Option<u64> = None;
+    let mut last_image_hash: Option<u64> = None;
+    // Whitespace-normalized text of the previous text capture, for coalesce_window_ms
+    let mut previous_capture: Option<(String, std::time::Instant)> = None;
+    info!("📋 Clipboard monitor using {} detection", clipboard_detection_mode());
+
     std::thread::spawn(move || {
-        let mut last_content = String::new();
-    
+        CLIPBOARD_MONITOR_RUNNING.store(true, std::sync::atomic::Ordering::SeqCst);
+        let _running = ClipboardMonitorGuard;
         loop {
             std::thread::sleep(std::time::Duration::from_millis(500));
             
-            // Read clipboard
-            let clipboard_result = app_handle.clipboard().read_text();
+            if monitor.lock().map(|m| m.paused).unwrap_or(false) {
+                continue;
+            }
+            
+            // Just resumed: adopt what set_clipboard_paused read instead of capturing it
+            if let Some(baseline) = monitor.lock().ok().and_then(|mut m| m.resume_baseline.take()) {
+                last_content = baseline.text;
+                last_image_hash = baseline.image_hash;
+                last_change_count = baseline.change_count;
+            }
+            
+            // Where the OS exposes a change counter, only read the clipboard when it moves
+            if let Some(change_count) = clipboard_change_count() {
+                if Some(change_count) == last_change_count {
+                    continue;
+                }
+                last_change_count = Some(change_count);
+            }
+            
+            let settings = app_handle
+                .state::<Arc<Mutex<AppSettings>>>()
+                .lock()
+                .map(|settings| settings.clone())
+                .unwrap_or_default();
+            
+            // Read clipboard, falling back to an image when there's no text
+            let captured = match app_handle.clipboard().read_text() {
+                Ok(content) if !content.is_empty() => {
+                    if content == last_content {
+                        None
+                    } else if is_coalesced_repeat(&content, &mut previous_capture, settings.coalesce_window_ms) {
+                        debug!("Ignoring near-duplicate clipboard text");
+                        last_content = content;
+                        None
+                    } else {
+                        last_content = content.clone();
+                        last_image_hash = None;
+                        let content_type = classify_content(&content).to_string();
+                        let size = content.len();
+                        Some((content, content_type, size))
+                    }
+                }
+                _ => match capture_clipboard_image(
+                    &app_handle,
+                    &mut last_image_hash,
+                    &settings.image_format,
+                    settings.image_quality,
+                ) {
+                    Some(captured) => {
+                        // Copying the previous text again after an image is a new copy
+                        last_content.clear();
+                        previous_capture = None;
+                        Some(captured)
+                    }
+                    None => None,
+                },
+            };
             
-            if let Ok(content) = clipboard_result {
-                if content != last_content && !content.is_empty() {
-                    last_content = content.clone();
+            if let Some((content, content_type, size)) = captured {
+                // Text one of our own commands just wrote is attributed to that command
+                let source = monitor
+                    .lock()
+                    .ok()
+                    .and_then(|mut monitor| monitor.pending_source.take())
+                    .filter(|(hash, _)| *hash == content_hash(&content))
+                    .map(|(_, source)| source)
+                    .unwrap_or(CLIPBOARD_SOURCE_SYSTEM);
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                
+                let item = ClipboardItem {
+                    id: format!("{}-{}", timestamp, uuid::Uuid::new_v4()),
+                    content,
+                    content_type,
+                    created_at: timestamp,
+                    last_accessed: timestamp,
+                    access_count: 0,
+                    source: source.to_string(),
+                    size,
+                    pinned: false,
+                    rating: 0,
+                    content_hash: String::new(), // Filled in by add_item
+                    pin_order: None,
+                    age_secs: None,
+                };
+                
+                // Add to database
+                if let Ok(mut db) = db.lock() {
+                    let evicted = match monitor.lock() {
+                        Ok(monitor) => {
+                            let incognito_ids = monitor.incognito.then_some(monitor.incognito_ids.as_slice());
+                            db.add_item(item.clone(), &settings, incognito_ids)
+                        }
+                        Err(_) => continue,
+                    };
+                    archive_evicted_items(&app_handle, &settings, &evicted);
                     
-                    let timestamp = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs();
+                    // A re-copy is merged into the existing entry, which is then the current item
+                    let hash = content_hash(&item.content);
+                    let current_id = db
+                        .items
+                        .iter()
+                        .find(|i| i.id == item.id || i.content_hash == hash)
+                        .map_or_else(|| item.id.clone(), |i| i.id.clone());
                     
-                    let item = ClipboardItem {
-                        id: format!("{}-{}", timestamp, uuid::Uuid::new_v4()),
-                        content: content.clone(),
-                        content_type: "text".to_string(),
-                        created_at: timestamp,
-                        last_accessed: timestamp,
-                        access_count: 0,
-                        source: "system".to_string(),
-                        size: content.len(),
-                    };
+                    // Remember in-session captures so they can be dropped when incognito ends
+                    let mut emit = true;
+                    if let Ok(mut monitor) = monitor.lock() {
+                        monitor.current_item_id = Some(current_id.clone());
+                        if monitor.incognito && db.items.iter().any(|i| i.id == item.id) {
+                            monitor.incognito_ids.push(item.id.clone());
+                        }
+                        // Picked up by the debounced saver
+                        monitor.dirty = true;
+                        emit = monitor.event_filter.is_empty()
+                            || monitor.event_filter.iter().any(|t| content_type_matches(&item.content_type, t));
+                    }
                     
-                    // Add to database
-                    if let Ok(mut db) = db.lock() {
-                        db.add_item(item.clone());
-                        
-                        // Save to file
-                        let db_path = get_db_path(&app_handle);
-                        let _ = save_db(&db, &db_path);
-                        
-                        // Emit event to frontend
-                        let _ = app_handle.emit("clipboard-update", item);
+                    // Mirror to companion devices if sharing is on
+                    broadcast_clipboard_item(&app_handle, &item);
+                    
+                    // Emit event to frontend, unless it only wants other content types
+                    if emit {
+                        let _ = app_handle.emit("clipboard-update", clipboard_update_payload(&item, &current_id));
+                    }
+                }
+            }
+        }
+    });
+}
+
+// Noisy apps re-set the clipboard with whitespace variants of what's already there.
+// Only the immediately preceding capture counts, so copying A, B, A again is still a new copy.
+// Records `content` as the previous capture unless it was coalesced into it
+fn is_coalesced_repeat(
+    content: &str,
+    previous: &mut Option<(String, std::time::Instant)>,
+    window_ms: u64,
+) -> bool {
+    if window_ms == 0 {
+        return false;
+    }
+    let window = std::time::Duration::from_millis(window_ms);
+    let normalized = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    let repeat = previous
+        .as_ref()
+        .is_some_and(|(text, at)| at.elapsed() < window && *text == normalized);
+    if !repeat {
+        *previous = Some((normalized, std::time::Instant::now()));
+    }
+    repeat
+}
+
+// ========== Clipboard Share (LAN websocket feed) ==========
+
+const DEFAULT_CLIPBOARD_SHARE_PORT: u16 = 3001;
+
+struct ClipboardShareState {
+    access_code: String,
+    sender: tokio::sync::broadcast::Sender<ClipboardItem>,
+    sessions: Arc<Mutex<Vec<tokio::task::AbortHandle>>>, // One per connected websocket
+}
+
+struct ClipboardShareHandle {
+    access_code: String,
+    port: u16,
+    server_handle: tokio::task::JoinHandle<()>,
+    sender: tokio::sync::broadcast::Sender<ClipboardItem>,
+    sessions: Arc<Mutex<Vec<tokio::task::AbortHandle>>>,
+}
+
+// Forward a captured text item to connected companion devices. Only text is shared, only
+// while an explicit share session is running, and never while incognito. Content types the
+// frontend filtered out of clipboard-update aren't shared either
+fn broadcast_clipboard_item(app_handle: &tauri::AppHandle, item: &ClipboardItem) {
+    if item.content_type != "text" && item.content_type != "url" {
+        return;
+    }
+    let monitor = app_handle.state::<Arc<Mutex<ClipboardMonitorState>>>();
+    let shareable = monitor.lock().is_ok_and(|monitor| {
+        !monitor.incognito
+            && (monitor.event_filter.is_empty()
+                || monitor.event_filter.iter().any(|t| content_type_matches(&item.content_type, t)))
+    });
+    if !shareable {
+        return;
+    }
+    let Some(share) = app_handle.try_state::<Arc<Mutex<Option<ClipboardShareHandle>>>>() else {
+        return;
+    };
+    if let Ok(share) = share.lock() {
+        if let Some(handle) = share.as_ref() {
+            // Err only means nobody is connected right now
+            let _ = handle.sender.send(item.clone());
+        }
+    };
+}
+
+// Best-effort LAN address so the companion device knows where to connect
+fn local_ip_address() -> Option<std::net::IpAddr> {
+    // Connecting a UDP socket doesn't send anything, it just picks the outbound interface
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+async fn clipboard_share_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<ClipboardShareState>>,
+    query: axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Response {
+    if query.get("code").map(|c| c.as_str()) != Some(state.access_code.as_str()) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let receiver = state.sender.subscribe();
+    let sessions = state.sessions.clone();
+    ws.on_upgrade(move |socket| async move {
+        // Run in a task of our own so stop_clipboard_share can abort it
+        let session = tokio::spawn(clipboard_share_session(socket, receiver));
+        if let Ok(mut sessions) = sessions.lock() {
+            sessions.retain(|session| !session.is_finished());
+            sessions.push(session.abort_handle());
+        }
+        let _ = session.await;
+    })
+}
+
+async fn clipboard_share_session(
+    socket: WebSocket,
+    mut receiver: tokio::sync::broadcast::Receiver<ClipboardItem>,
+) {
+    let (mut outgoing, mut incoming) = socket.split();
+    info!("📱 Clipboard share client connected");
+
+    loop {
+        tokio::select! {
+            item = receiver.recv() => {
+                match item {
+                    Ok(item) => {
+                        let Ok(json) = serde_json::to_string(&item) else { continue };
+                        if outgoing.send(Message::Text(json)).await.is_err() {
+                            break;
+                        }
                     }
+                    // A slow client missed some items; keep going with the newest
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            message = incoming.next() => {
+                match message {
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                    Some(Ok(_)) => {} // Clients only listen
                 }
             }
         }
+    }
+
+    info!("📱 Clipboard share client disconnected");
+}
+
+// Set while start_clipboard_share is between its check and storing the handle
+static CLIPBOARD_SHARE_STARTING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+// Clears CLIPBOARD_SHARE_STARTING on every return path, errors included
+struct ClipboardShareStartGuard;
+
+impl Drop for ClipboardShareStartGuard {
+    fn drop(&mut self) {
+        CLIPBOARD_SHARE_STARTING.store(false, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+// Start broadcasting new clipboard items over a LAN websocket, gated by an access code
+#[tauri::command]
+async fn start_clipboard_share(
+    state: tauri::State<'_, Arc<Mutex<Option<ClipboardShareHandle>>>>,
+    port: Option<u16>,
+) -> Result<serde_json::Value, String> {
+    // Only one start at a time, or a second server would replace the first's handle and be unstoppable
+    if CLIPBOARD_SHARE_STARTING.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        return Err("Clipboard share is already starting".to_string());
+    }
+    let _starting = ClipboardShareStartGuard;
+
+    if state.lock().map_err(|e| e.to_string())?.is_some() {
+        return Err("Clipboard share is already running".to_string());
+    }
+
+    let port = port.unwrap_or(DEFAULT_CLIPBOARD_SHARE_PORT);
+    let access_code = generate_access_code();
+    let (sender, _) = tokio::sync::broadcast::channel(32);
+    let sessions = Arc::new(Mutex::new(Vec::new()));
+
+    let share_state = Arc::new(ClipboardShareState {
+        access_code: access_code.clone(),
+        sender: sender.clone(),
+        sessions: sessions.clone(),
+    });
+    let app = Router::new()
+        .route("/ws", get(clipboard_share_ws))
+        .layer(CorsLayer::permissive())
+        .with_state(share_state);
+
+    // Bind on all interfaces so devices on the LAN can reach it
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port))
+        .await
+        .map_err(|e| format!("Failed to bind clipboard share port {}: {}", port, e))?;
+    let server_handle = tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            error!("❌ Clipboard share server error: {}", e);
+        }
     });
+
+    {
+        let mut share = match state.lock() {
+            Ok(share) => share,
+            Err(e) => {
+                // Dropping the JoinHandle wouldn't stop the server
+                server_handle.abort();
+                return Err(e.to_string());
+            }
+        };
+        *share = Some(ClipboardShareHandle {
+            access_code: access_code.clone(),
+            port,
+            server_handle,
+            sender,
+            sessions,
+        });
+    }
+
+    let host = local_ip_address()
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "localhost".to_string());
+    info!("📋 Clipboard share started on ws://{}:{}/ws", host, port);
+
+    Ok(serde_json::json!({
+        "code": access_code,
+        "port": port,
+        "url": format!("ws://{}:{}/ws?code={}", host, port, access_code),
+    }))
+}
+
+#[tauri::command]
+async fn stop_clipboard_share(
+    state: tauri::State<'_, Arc<Mutex<Option<ClipboardShareHandle>>>>,
+) -> Result<(), String> {
+    let handle = state.lock().map_err(|e| e.to_string())?.take();
+    match handle {
+        Some(handle) => {
+            handle.server_handle.abort();
+            // Upgraded websockets outlive the server task, so end them explicitly
+            for session in handle.sessions.lock().map_err(|e| e.to_string())?.drain(..) {
+                session.abort();
+            }
+            info!("📋 Clipboard share stopped (code {}, port {})", handle.access_code, handle.port);
+            Ok(())
+        }
+        None => Err("Clipboard share is not running".to_string()),
+    }
 }
 
 // ========== HLS Screen Sharing Server ==========
 
+// HLS clients typically request segments every 2 seconds, so 15 seconds is a safe timeout
+const DEFAULT_VIEWER_TIMEOUT_SECS: u64 = 15;
+const DEFAULT_VIEWER_SWEEP_INTERVAL_SECS: u64 = 5;
+
+// Optional settings for start_hls_server_cmd; everything falls back to the defaults above
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct HlsStartOptions {
+    viewer_timeout_secs: Option<u64>,
+    viewer_sweep_interval_secs: Option<u64>,
+    // "auto" (default), "software", or an explicit FFmpeg encoder name like "h264_nvenc"
+    encoder: Option<String>,
+    // Fixed access code for repeat sessions; a random one is generated when omitted
+    access_code: Option<String>,
+    // Requested localtunnel subdomain for a stable URL; localtunnel picks another if it's taken
+    subdomain: Option<String>,
+    // Share a single window (id from list_capturable_windows) instead of the whole display
+    window_id: Option<u32>,
+    // Encode every rendition in HLS_RENDITIONS behind a master playlist so players can adapt
+    adaptive: Option<bool>,
+    // Expert escape hatch: extra FFmpeg output options inserted before the playlist path
+    extra_ffmpeg_args: Vec<String>,
+    // Serve /preview.gif; off by default since every refresh runs an extra FFmpeg encode
+    preview_gif: Option<bool>,
+    // Log every playlist/segment request; off by default since players poll constantly
+    verbose_hls_logging: Option<bool>,
+    // Friendly name shown to viewers, e.g. "Alice's MacBook - Design Review"
+    stream_title: Option<String>,
+    // Mix what the computer is playing into the stream (see system_audio_input_args).
+    // macOS has no native loopback, so this needs a virtual device like BlackHole installed
+    include_system_audio: Option<bool>,
+    // Only these viewer addresses ("203.0.113.7" or "203.0.113.0/24", IPv6 too) may connect,
+    // checked before the code. Empty allows everyone. Local players need 127.0.0.1 listed
+    allowed_cidrs: Vec<String>,
+}
+
+// Cookie issued on the first playlist fetch so viewers sharing an IP (e.g. behind the tunnel) are counted separately
+const VIEWER_SESSION_COOKIE: &str = "pf_viewer";
+
+#[derive(Debug, Clone)]
+struct ViewerInfo {
+    ip: String,
+    last_seen: SystemTime,
+    issued_session: Option<String>, // Session cookie issued by the request that last refreshed this entry
+}
+
 #[derive(Debug, Clone)]
 struct HlsServerState {
     access_code: String,
     port: u16,
     public_dir: PathBuf,
-    viewers: Arc<Mutex<std::collections::HashMap<String, std::time::SystemTime>>>, // IP -> last seen
+    viewers: Arc<Mutex<std::collections::HashMap<String, ViewerInfo>>>, // viewer id -> IP + last seen
+    viewer_timeout_secs: u64,
+    viewer_sweep_interval_secs: u64,
+    adaptive: bool, // /stream.m3u8 serves the master playlist instead of a single rendition
+    preview_gif: bool,
+    preview_lock: Arc<tokio::sync::Mutex<()>>, // One GIF encode at a time
+    segment_prefix: String, // Only segments named "<prefix>_..." belong to this session
+    viewer_tokens: Arc<Mutex<std::collections::HashSet<String>>>, // Unredeemed one-time tokens
+    token_sessions: Arc<Mutex<std::collections::HashSet<String>>>, // Viewer sessions admitted by a token
+    ended: Arc<std::sync::atomic::AtomicBool>, // Set by stop_hls_server_cmd just before teardown
+    paused: Arc<std::sync::atomic::AtomicBool>, // Playlists are frozen while FFmpeg keeps running
+    paused_playlists: Arc<Mutex<std::collections::HashMap<String, Vec<u8>>>>, // Served while paused
+    verbose_logging: bool, // Per-request logs; errors are logged either way
+    stream_title: Option<String>,
+    allowed_cidrs: Vec<(std::net::IpAddr, u8)>, // Network and prefix length; empty allows everyone
 }
 
 struct HlsServerHandle {
@@ -582,8 +4356,17 @@ struct HlsServerHandle {
     port: u16,
     tunnel_url: Option<String>,
     tunnel_domain: Option<String>,
+    tunnel_password: Option<String>, // What viewers type on localtunnel's interstitial page
     public_dir: PathBuf,
-    viewers: Arc<Mutex<std::collections::HashMap<String, std::time::SystemTime>>>,
+    viewers: Arc<Mutex<std::collections::HashMap<String, ViewerInfo>>>,
+    viewer_tokens: Arc<Mutex<std::collections::HashSet<String>>>,
+    ended: Arc<std::sync::atomic::AtomicBool>,
+    paused: Arc<std::sync::atomic::AtomicBool>,
+    paused_playlists: Arc<Mutex<std::collections::HashMap<String, Vec<u8>>>>,
+    stream_title: Option<String>,
+    ffmpeg_log: Arc<Mutex<std::collections::VecDeque<String>>>,
+    // What the capture device actually delivers, e.g. "1280x720 @ 30 fps"
+    actual_resolution: Arc<Mutex<Option<String>>>,
 }
 
 // Check if FFmpeg is available
@@ -600,32 +4383,156 @@ async fn check_ffmpeg() -> Result<bool, String> {
     }
 }
 
+// Builds older than this lack options start_ffmpeg relies on (e.g. -hls_segment_type)
+const MIN_FFMPEG_MAJOR_VERSION: u32 = 4;
+
+// Parsed `ffmpeg -version` plus what the build can encode and stream over
+#[derive(Debug, Clone, Serialize)]
+pub struct FfmpegInfo {
+    pub version: String,
+    pub major_version: Option<u32>,
+    pub outdated: bool,
+    pub configuration: Vec<String>, // --enable-* flags the build was configured with
+    pub hardware_encoders: Vec<String>,
+    pub protocols: Vec<String>,
+}
+
+// "ffmpeg version 6.1.1 Copyright ..." / "ffmpeg version n6.1-3-g..." -> ("6.1.1", Some(6))
+fn parse_ffmpeg_version(output: &str) -> (String, Option<u32>) {
+    let version = output
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("ffmpeg version "))
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or("unknown")
+        .to_string();
+    let digits: String = version
+        .trim_start_matches('n')
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    // Git snapshots ("2024-01-01-git-...") have no release number
+    let major = (!version.contains("-git-")).then(|| digits.parse().ok()).flatten();
+    (version, major)
+}
+
+async fn probe_ffmpeg_protocols() -> Result<Vec<String>, String> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-protocols"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffmpeg -protocols: {}", e))?;
+
+    // Names are indented under "Input:" and "Output:" headers
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut protocols: Vec<String> = stdout
+        .lines()
+        .filter(|line| line.starts_with(' '))
+        .map(|line| line.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    protocols.sort();
+    protocols.dedup();
+    Ok(protocols)
+}
+
+// Tauri command exposing FFmpeg's version and capabilities for diagnostics
+#[tauri::command]
+async fn get_ffmpeg_info() -> Result<FfmpegInfo, String> {
+    let output = Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .await
+        .map_err(|e| format!("FFmpeg not found: {}", e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (version, major_version) = parse_ffmpeg_version(&stdout);
+
+    let configuration = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("configuration:"))
+        .map(|flags| {
+            flags
+                .split_whitespace()
+                .filter(|flag| flag.starts_with("--enable-"))
+                .map(|flag| flag.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let encoders = probe_ffmpeg_encoders().await.unwrap_or_default();
+    let hardware_encoders = hardware_encoder_candidates()
+        .iter()
+        .filter(|candidate| encoders.iter().any(|name| name == *candidate))
+        .map(|name| name.to_string())
+        .collect();
+
+    Ok(FfmpegInfo {
+        version,
+        major_version,
+        outdated: major_version.is_some_and(|major| major < MIN_FFMPEG_MAJOR_VERSION),
+        configuration,
+        hardware_encoders,
+        protocols: probe_ffmpeg_protocols().await.unwrap_or_default(),
+    })
+}
+
+// avfoundation numbers cameras and screens together, so a fixed index may well be a webcam.
+// Look up the "Capture screen" entry instead
+#[cfg(target_os = "macos")]
+async fn default_capture_device() -> Result<Option<String>, String> {
+    let devices = list_ffmpeg_devices().await?;
+    let index_of = |kind: &str, wanted: &dyn Fn(&str) -> bool| {
+        devices[kind].as_array().and_then(|list| {
+            list.iter()
+                .find(|d| d["name"].as_str().is_some_and(wanted))
+                .and_then(|d| d["index"].as_u64())
+        })
+    };
+
+    let screen = index_of("video", &|name| name.starts_with("Capture screen"))
+        .ok_or_else(|| "No screen capture device found; check Screen Recording permission".to_string())?;
+    // Any microphone is better than none; "none" tells avfoundation to skip audio
+    let audio = index_of("audio", &|_| true)
+        .map(|index| index.to_string())
+        .unwrap_or_else(|| "none".to_string());
+
+    let device = format!("{}:{}", screen, audio);
+    info!("🖥️  Defaulting to screen capture device {}", device);
+    Ok(Some(device))
+}
+
+// Other platforms capture the desktop directly and don't need a device index
+#[cfg(not(target_os = "macos"))]
+async fn default_capture_device() -> Result<Option<String>, String> {
+    Ok(None)
+}
+
 // List available FFmpeg devices (macOS avfoundation)
 #[tauri::command]
 async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
-    eprintln!("🔍 Starting FFmpeg device detection...");
+    debug!("🔍 Starting FFmpeg device detection...");
     
     #[cfg(target_os = "macos")]
     {
-        eprintln!("📱 Running on macOS, using avfoundation");
+        debug!("📱 Running on macOS, using avfoundation");
         let output = Command::new("ffmpeg")
-            .args(&["-f", "avfoundation", "-list_devices", "true", "-i", ""])
+            .args(["-f", "avfoundation", "-list_devices", "true", "-i", ""])
             .output()
             .await
             .map_err(|e| {
-                eprintln!("❌ Failed to run ffmpeg command: {}", e);
+                error!("❌ Failed to run ffmpeg command: {}", e);
                 format!("Failed to run ffmpeg: {}", e)
             })?;
         
-        eprintln!("✅ FFmpeg command executed, exit code: {:?}", output.status.code());
+        debug!("✅ FFmpeg command executed, exit code: {:?}", output.status.code());
         
         let stderr = String::from_utf8_lossy(&output.stderr);
-        eprintln!("📄 FFmpeg stderr output ({} bytes):", stderr.len());
-        eprintln!("--- START FFmpeg Output ---");
+        debug!("📄 FFmpeg stderr output ({} bytes):", stderr.len());
+        debug!("--- START FFmpeg Output ---");
         for (i, line) in stderr.lines().enumerate() {
-            eprintln!("Line {}: {}", i + 1, line);
+            debug!("Line {}: {}", i + 1, line);
         }
-        eprintln!("--- END FFmpeg Output ---");
+        debug!("--- END FFmpeg Output ---");
         
         // Parse video devices
         let mut video_devices = Vec::new();
@@ -633,16 +4540,16 @@ async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
         let mut in_video_section = false;
         let mut in_audio_section = false;
         
-        eprintln!("🔎 Parsing device list...");
+        debug!("🔎 Parsing device list...");
         for (line_num, line) in stderr.lines().enumerate() {
             if line.contains("AVFoundation video devices:") {
-                eprintln!("📹 Found video devices section at line {}", line_num + 1);
+                debug!("📹 Found video devices section at line {}", line_num + 1);
                 in_video_section = true;
                 in_audio_section = false;
                 continue;
             }
             if line.contains("AVFoundation audio devices:") {
-                eprintln!("🔊 Found audio devices section at line {}", line_num + 1);
+                debug!("🔊 Found audio devices section at line {}", line_num + 1);
                 in_audio_section = true;
                 in_video_section = false;
                 continue;
@@ -669,7 +4576,7 @@ async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
                                     let name = after_first[name_start..].trim().to_string();
                                     
                                     if !name.is_empty() {
-                                        eprintln!("  ✓ Found device: [{}] \"{}\" (section: {})", 
+                                        debug!("  ✓ Found device: [{}] \"{}\" (section: {})", 
                                             index, name, 
                                             if in_video_section { "video" } else { "audio" });
                                         if in_video_section {
@@ -684,30 +4591,30 @@ async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
                                             }));
             }
         } else {
-                                        eprintln!("  ⚠️  Line {}: Empty device name: {}", line_num + 1, line);
+                                        warn!("  ⚠️  Line {}: Empty device name: {}", line_num + 1, line);
                                     }
         } else {
-                                    eprintln!("  ⚠️  Line {}: Could not parse index '{}' from: {}", line_num + 1, index_str, line);
+                                    warn!("  ⚠️  Line {}: Could not parse index '{}' from: {}", line_num + 1, index_str, line);
             }
         } else {
-                                eprintln!("  ⚠️  Line {}: No closing bracket for device index: {}", line_num + 1, line);
+                                warn!("  ⚠️  Line {}: No closing bracket for device index: {}", line_num + 1, line);
                             }
         } else {
-                            eprintln!("  ⚠️  Line {}: No second bracket found: {}", line_num + 1, line);
+                            warn!("  ⚠️  Line {}: No second bracket found: {}", line_num + 1, line);
                         }
                     }
                 }
             }
         }
         
-        eprintln!("📊 Parsing complete:");
-        eprintln!("  Video devices found: {}", video_devices.len());
+        debug!("📊 Parsing complete:");
+        debug!("  Video devices found: {}", video_devices.len());
         for device in &video_devices {
-            eprintln!("    - [{}] {}", device["index"], device["name"]);
+            debug!("    - [{}] {}", device["index"], device["name"]);
         }
-        eprintln!("  Audio devices found: {}", audio_devices.len());
+        debug!("  Audio devices found: {}", audio_devices.len());
         for device in &audio_devices {
-            eprintln!("    - [{}] {}", device["index"], device["name"]);
+            debug!("    - [{}] {}", device["index"], device["name"]);
         }
         
         let result = serde_json::json!({
@@ -715,13 +4622,13 @@ async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
             "audio": audio_devices
         });
         
-        eprintln!("✅ Returning device list to frontend");
+        debug!("✅ Returning device list to frontend");
         Ok(result)
     }
     
     #[cfg(target_os = "windows")]
     {
-        eprintln!("🪟 Running on Windows, detecting audio devices");
+        debug!("🪟 Running on Windows, detecting audio devices");
         
         // On Windows, video is always "desktop" (gdigrab), but we can list audio devices
         // First, add a default "Desktop" video device
@@ -747,8 +4654,8 @@ async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
                 // Check if wasapi is actually supported (not "Unknown input format")
                 if !stderr.contains("Unknown input format") && !stderr.contains("Invalid argument") {
                     wasapi_available = true;
-                    eprintln!("✅ wasapi is available, using it for device detection");
-                    eprintln!("📄 FFmpeg wasapi output ({} bytes):", stderr.len());
+                    debug!("✅ wasapi is available, using it for device detection");
+                    debug!("📄 FFmpeg wasapi output ({} bytes):", stderr.len());
                     
                     // Parse WASAPI audio devices
                     let mut device_index = 0;
@@ -759,7 +4666,7 @@ async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
                                 if let Some(quote_end) = after_quote.find('"') {
                                     let device_name = after_quote[..quote_end].to_string();
                                     if !device_name.is_empty() {
-                                        eprintln!("  ✓ Found wasapi audio device: [{}] \"{}\"", device_index, device_name);
+                                        debug!("  ✓ Found wasapi audio device: [{}] \"{}\"", device_index, device_name);
                                         audio_devices.push(serde_json::json!({
                                             "index": device_index,
                                             "name": device_name
@@ -771,17 +4678,17 @@ async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
                         }
                     }
                 } else {
-                    eprintln!("⚠️  wasapi not supported in this FFmpeg build");
+                    warn!("⚠️  wasapi not supported in this FFmpeg build");
                 }
             }
             Err(e) => {
-                eprintln!("⚠️  Failed to query wasapi: {}", e);
+                warn!("⚠️  Failed to query wasapi: {}", e);
             }
         }
         
         // If wasapi didn't work or found no devices, try dshow
         if !wasapi_available || audio_devices.is_empty() {
-            eprintln!("🔄 Trying dshow as fallback...");
+            debug!("🔄 Trying dshow as fallback...");
             let dshow_output = Command::new("ffmpeg")
                 .args(&["-f", "dshow", "-list_devices", "true", "-i", "dummy"])
                 .output()
@@ -789,7 +4696,7 @@ async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
             
             if let Ok(dshow_output) = dshow_output {
                 let stderr = String::from_utf8_lossy(&dshow_output.stderr);
-                eprintln!("📄 FFmpeg dshow output ({} bytes):", stderr.len());
+                debug!("📄 FFmpeg dshow output ({} bytes):", stderr.len());
                 
                 // If we already have devices from wasapi, don't overwrite
                 if audio_devices.is_empty() {
@@ -805,7 +4712,7 @@ async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
                                     if !device_name.is_empty() && !device_name.contains("USB") && !device_name.contains("UVC") {
                                         // Skip video devices (webcams), only capture audio devices
                                         // This is a heuristic - dshow doesn't clearly separate audio/video
-                                        eprintln!("  ✓ Found dshow device: [{}] \"{}\"", device_index, device_name);
+                                        debug!("  ✓ Found dshow device: [{}] \"{}\"", device_index, device_name);
                                         audio_devices.push(serde_json::json!({
                                             "index": device_index,
                                             "name": device_name
@@ -822,21 +4729,21 @@ async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
         
         // If still no devices found, add a default option
         if audio_devices.is_empty() {
-            eprintln!("⚠️  No audio devices found, adding default option");
+            warn!("⚠️  No audio devices found, adding default option");
             audio_devices.push(serde_json::json!({
                 "index": 0,
                 "name": "Default Audio Device"
             }));
         }
         
-        eprintln!("📊 Parsing complete:");
-        eprintln!("  Video devices found: {}", video_devices.len());
+        debug!("📊 Parsing complete:");
+        debug!("  Video devices found: {}", video_devices.len());
         for device in &video_devices {
-            eprintln!("    - [{}] {}", device["index"], device["name"]);
+            debug!("    - [{}] {}", device["index"], device["name"]);
         }
-        eprintln!("  Audio devices found: {}", audio_devices.len());
+        debug!("  Audio devices found: {}", audio_devices.len());
         for device in &audio_devices {
-            eprintln!("    - [{}] {}", device["index"], device["name"]);
+            debug!("    - [{}] {}", device["index"], device["name"]);
         }
         
         let result = serde_json::json!({
@@ -844,13 +4751,13 @@ async fn list_ffmpeg_devices() -> Result<serde_json::Value, String> {
             "audio": audio_devices
         });
         
-        eprintln!("✅ Returning device list to frontend");
+        debug!("✅ Returning device list to frontend");
         Ok(result)
     }
     
     #[cfg(not(any(target_os = "macos", target_os = "windows")))]
     {
-        eprintln!("⚠️  Not running on macOS or Windows, returning empty device list");
+        warn!("⚠️  Not running on macOS or Windows, returning empty device list");
         // For other platforms (Linux), return empty lists
         Ok(serde_json::json!({
             "video": [],
@@ -916,53 +4823,104 @@ async fn check_localtunnel() -> Result<bool, String> {
     }
 }
 
+// Localtunnel subdomains must be valid DNS labels
+fn validate_subdomain(subdomain: &str) -> Result<(), String> {
+    let valid = (4..=63).contains(&subdomain.len())
+        && subdomain.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !subdomain.starts_with('-')
+        && !subdomain.ends_with('-');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid subdomain '{}': use 4-63 lowercase letters, digits or '-'",
+            subdomain
+        ))
+    }
+}
+
+// Arguments for `npx localtunnel`, optionally asking for a fixed subdomain
+fn localtunnel_args(port: u16, subdomain: Option<&str>) -> Vec<String> {
+    let mut args = vec![
+        "-y".to_string(),
+        "localtunnel".to_string(),
+        "--port".to_string(),
+        port.to_string(),
+    ];
+    if let Some(subdomain) = subdomain {
+        args.extend(["--subdomain".to_string(), subdomain.to_string()]);
+    }
+    args
+}
+
+const TUNNEL_PASSWORD_URL: &str = "https://loca.lt/mytunnelpassword";
+const TUNNEL_PASSWORD_TIMEOUT_SECS: u64 = 5;
+
+// localtunnel's interstitial asks for the host's public IP, which this endpoint returns
+async fn fetch_tunnel_password() -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(TUNNEL_PASSWORD_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client.get(TUNNEL_PASSWORD_URL).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Tunnel password lookup returned {}", response.status()));
+    }
+    let password = response.text().await.map_err(|e| e.to_string())?.trim().to_string();
+    if password.is_empty() {
+        return Err("Tunnel password lookup returned nothing".to_string());
+    }
+    Ok(password)
+}
+
 // Start localtunnel and parse the URL
-async fn start_localtunnel(port: u16) -> anyhow::Result<(tokio::process::Child, String, String)> {
+async fn start_localtunnel(port: u16, subdomain: Option<&str>) -> anyhow::Result<(tokio::process::Child, String, String)> {
     #[cfg(target_os = "windows")]
     {
         // On Windows, try multiple methods to run npx
         // Method 1: Try cmd.exe /C npx (works if npx is in PATH)
         let mut cmd = Command::new("cmd");
-        cmd.args(&["/C", "npx", "-y", "localtunnel", "--port", &port.to_string()]);
+        cmd.args(["/C", "npx"]);
+        cmd.args(localtunnel_args(port, subdomain));
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
         cmd.stdin(Stdio::null()); // Prevent cmd from waiting for input
         
         match cmd.spawn() {
             Ok(child) => {
-                eprintln!("✅ Started localtunnel via cmd.exe /C npx");
+                info!("✅ Started localtunnel via cmd.exe /C npx");
                 return start_localtunnel_common(child, port).await;
             }
             Err(e1) => {
-                eprintln!("⚠️  Failed to start localtunnel via cmd.exe /C npx: {}", e1);
+                warn!("⚠️  Failed to start localtunnel via cmd.exe /C npx: {}", e1);
                 
                 // Method 2: Try npx.cmd directly (Windows-specific)
                 let mut cmd2 = Command::new("npx.cmd");
-                cmd2.args(&["-y", "localtunnel", "--port", &port.to_string()]);
+                cmd2.args(localtunnel_args(port, subdomain));
                 cmd2.stdout(Stdio::piped());
                 cmd2.stderr(Stdio::piped());
                 
                 match cmd2.spawn() {
                     Ok(child) => {
-                        eprintln!("✅ Started localtunnel via npx.cmd");
+                        info!("✅ Started localtunnel via npx.cmd");
                         return start_localtunnel_common(child, port).await;
                     }
                     Err(e2) => {
-                        eprintln!("⚠️  Failed to start localtunnel via npx.cmd: {}", e2);
+                        warn!("⚠️  Failed to start localtunnel via npx.cmd: {}", e2);
                         
                         // Method 3: Try npx directly (might work if Node.js is in PATH)
                         let mut cmd3 = Command::new("npx");
-                        cmd3.args(&["-y", "localtunnel", "--port", &port.to_string()]);
+                        cmd3.args(localtunnel_args(port, subdomain));
                         cmd3.stdout(Stdio::piped());
                         cmd3.stderr(Stdio::piped());
                         
                         match cmd3.spawn() {
                             Ok(child) => {
-                                eprintln!("✅ Started localtunnel via npx directly");
+                                info!("✅ Started localtunnel via npx directly");
                                 return start_localtunnel_common(child, port).await;
                             }
                             Err(e3) => {
-                                eprintln!("⚠️  Failed to start localtunnel via npx directly: {}", e3);
+                                warn!("⚠️  Failed to start localtunnel via npx directly: {}", e3);
                                 return Err(anyhow::anyhow!(
                                     "Failed to start localtunnel. Tried multiple methods:\n\
                                     1. cmd.exe /C npx: {}\n\
@@ -984,7 +4942,7 @@ async fn start_localtunnel(port: u16) -> anyhow::Result<(tokio::process::Child,
     {
         // On macOS/Linux, use npx directly
         let mut cmd = Command::new("npx");
-        cmd.args(&["-y", "localtunnel", "--port", &port.to_string()]);
+        cmd.args(localtunnel_args(port, subdomain));
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
         
@@ -995,7 +4953,7 @@ async fn start_localtunnel(port: u16) -> anyhow::Result<(tokio::process::Child,
             )
         })?;
         
-        return start_localtunnel_common(child, port).await;
+        start_localtunnel_common(child, port).await
     }
 }
 
@@ -1059,7 +5017,7 @@ async fn start_localtunnel_common(
                 line_result = lines.next_line() => {
                     match line_result {
                         Ok(Some(line)) => {
-                            eprintln!("Localtunnel stderr: {}", line);
+                            debug!("Localtunnel stderr: {}", line);
                             if let Some((url, domain)) = extract_url_and_domain(&line) {
                                 found_url = Some((url, domain));
                                 stderr_consumed = true;
@@ -1097,7 +5055,7 @@ async fn start_localtunnel_common(
                     line_result = lines.next_line() => {
                         match line_result {
                             Ok(Some(line)) => {
-                                eprintln!("Localtunnel stdout: {}", line);
+                                debug!("Localtunnel stdout: {}", line);
                                 if let Some((url, domain)) = extract_url_and_domain(&line) {
                                     found_url = Some((url, domain));
                                     stdout_consumed = true;
@@ -1123,10 +5081,65 @@ async fn start_localtunnel_common(
     } else {
         // Wait a bit more and check if process is still running
         tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        // Don't leave a tunnel running that nobody holds a handle to
+        let pid = child.id();
+        if let Err(e) = kill_process_forcefully(&mut child, pid).await {
+            warn!("⚠️  Failed to stop localtunnel: {}", e);
+        }
         Err(anyhow::anyhow!("Could not parse localtunnel URL from output. Check if localtunnel is working correctly."))
     }
 }
 
+// Shown verbatim to anyone with the link, so keep it short and printable
+const MAX_STREAM_TITLE_CHARS: usize = 100;
+
+fn validate_stream_title(title: &str) -> Result<String, String> {
+    if title.chars().count() > MAX_STREAM_TITLE_CHARS {
+        return Err(format!("Stream title must be at most {} characters", MAX_STREAM_TITLE_CHARS));
+    }
+    if title.chars().any(char::is_control) {
+        return Err("Stream title contains control characters".to_string());
+    }
+    Ok(title.to_string())
+}
+
+// A supplied code ends up in URLs and cookies, so stick to what generate_access_code produces
+fn validate_access_code(code: &str) -> Result<(), String> {
+    if code.len() < 4 || code.len() > 32 {
+        return Err("Access code must be 4 to 32 characters long".to_string());
+    }
+    if !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("Access code may only contain letters and digits".to_string());
+    }
+    Ok(())
+}
+
+// Options that would move or reshape the HLS output the server expects to find
+const RESERVED_FFMPEG_OPTIONS: &[&str] = &["-f", "-i", "-y", "-master_pl_name", "-var_stream_map"];
+
+// Extra args are passed as argv entries (no shell), but must not redirect the HLS output
+fn validate_extra_ffmpeg_args(args: &[String]) -> Result<(), String> {
+    let mut previous_was_option = false;
+    for arg in args {
+        if arg.is_empty() || arg.chars().any(|c| c.is_control()) {
+            return Err(format!("Invalid FFmpeg argument: {:?}", arg));
+        }
+        if arg.starts_with('-') {
+            if RESERVED_FFMPEG_OPTIONS.contains(&arg.as_str()) || arg.starts_with("-hls") {
+                return Err(format!("FFmpeg option {} is managed by PathFinder", arg));
+            }
+            previous_was_option = true;
+        } else {
+            // A bare argument that isn't an option value would become a second output file
+            if !previous_was_option {
+                return Err(format!("FFmpeg argument {} must follow an option", arg));
+            }
+            previous_was_option = false;
+        }
+    }
+    Ok(())
+}
+
 // Generate random 6-character access code
 fn generate_access_code() -> String {
     use rand::Rng;
@@ -1211,6 +5224,198 @@ async fn get_windows_audio_device_name(audio_index: usize) -> Option<String> {
     }
 }
 
+// A top-level window that can be shared on its own (macOS only)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturableWindow {
+    pub id: u32,
+    pub owner: String,
+    pub title: String,
+    // Bounds in screen points, as reported by CGWindowList
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    // Pixels per point of the main display, to map bounds onto the captured frame
+    pub scale: f64,
+    // Entirely on the main display; capture crops that display, so other windows can't be shared
+    #[serde(default)]
+    pub on_main_display: bool,
+}
+
+// Enumerate on-screen windows via CGWindowListCopyWindowInfo from a JXA script
+#[cfg(target_os = "macos")]
+async fn query_capturable_windows() -> Result<Vec<CapturableWindow>, String> {
+    const SCRIPT: &str = r#"
+ObjC.import('CoreGraphics');
+ObjC.import('AppKit');
+// The first screen is the main display, whose top-left corner is the origin of window bounds
+var main = $.NSScreen.screens.objectAtIndex(0);
+var scale = main.backingScaleFactor;
+var mainWidth = main.frame.size.width;
+var mainHeight = main.frame.size.height;
+var list = ObjC.castRefToObject($.CGWindowListCopyWindowInfo($.kCGWindowListOptionOnScreenOnly | $.kCGWindowListExcludeDesktopElements, $.kCGNullWindowID));
+var windows = [];
+for (var i = 0; i < list.count; i++) {
+    var w = list.objectAtIndex(i);
+    var layer = w.objectForKey('kCGWindowLayer');
+    if (!layer || layer.js !== 0) continue;
+    var bounds = w.objectForKey('kCGWindowBounds');
+    var name = w.objectForKey('kCGWindowName');
+    var x = bounds.objectForKey('X').js;
+    var y = bounds.objectForKey('Y').js;
+    var width = bounds.objectForKey('Width').js;
+    var height = bounds.objectForKey('Height').js;
+    windows.push({
+        id: w.objectForKey('kCGWindowNumber').js,
+        owner: w.objectForKey('kCGWindowOwnerName').js || '',
+        title: name ? name.js : '',
+        x: x,
+        y: y,
+        width: width,
+        height: height,
+        scale: scale,
+        on_main_display: x >= 0 && y >= 0 && x + width <= mainWidth && y + height <= mainHeight
+    });
+}
+JSON.stringify(windows);
+"#;
+
+    let output = Command::new("osascript")
+        .args(["-l", "JavaScript", "-e", SCRIPT])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Window listing failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("Unexpected window list: {}", e))
+}
+
+#[cfg(not(target_os = "macos"))]
+async fn query_capturable_windows() -> Result<Vec<CapturableWindow>, String> {
+    Err("Window capture is only supported on macOS".to_string())
+}
+
+#[tauri::command]
+async fn list_capturable_windows() -> Result<Vec<CapturableWindow>, String> {
+    query_capturable_windows().await
+}
+
+// A screen avfoundation can capture, with the name the user knows it by
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureDisplay {
+    // avfoundation video index, i.e. the part before ':' in the device string
+    pub index: u64,
+    pub device_name: String, // "Capture screen N"
+    pub label: String, // "Built-in Retina Display", "DELL U2720Q", ...
+    pub builtin: bool,
+}
+
+// NSScreen names and built-in flags, in the same main-display-first order avfoundation
+// uses for its "Capture screen N" devices
+#[cfg(target_os = "macos")]
+async fn query_screen_names() -> Result<Vec<(String, bool)>, String> {
+    const SCRIPT: &str = r#"
+ObjC.import('CoreGraphics');
+ObjC.import('AppKit');
+var screens = $.NSScreen.screens;
+var result = [];
+for (var i = 0; i < screens.count; i++) {
+    var screen = screens.objectAtIndex(i);
+    var id = screen.deviceDescription.objectForKey('NSScreenNumber').js;
+    result.push([screen.localizedName ? screen.localizedName.js : '', !!$.CGDisplayIsBuiltin(id)]);
+}
+JSON.stringify(result);
+"#;
+
+    let output = Command::new("osascript")
+        .args(["-l", "JavaScript", "-e", SCRIPT])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Display listing failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("Unexpected display list: {}", e))
+}
+
+// Screens to offer for sharing; pass `index` as the video half of the capture device
+#[tauri::command]
+async fn list_capture_displays() -> Result<Vec<CaptureDisplay>, String> {
+    #[cfg(target_os = "macos")]
+    {
+        let devices = list_ffmpeg_devices().await?;
+        // Labels are a nicety; without them the screens are still listed
+        let names = query_screen_names().await.unwrap_or_else(|e| {
+            warn!("⚠️  Could not read display names: {}", e);
+            Vec::new()
+        });
+        let screens = devices["video"]
+            .as_array()
+            .map(|list| {
+                list.iter()
+                    .filter_map(|d| Some((d["index"].as_u64()?, d["name"].as_str()?.to_string())))
+                    .filter(|(_, name)| name.starts_with("Capture screen"))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        Ok(screens
+            .into_iter()
+            .enumerate()
+            .map(|(n, (index, device_name))| {
+                let (label, builtin) = match names.get(n).cloned() {
+                    Some((name, builtin)) if !name.is_empty() => (name, builtin),
+                    Some((_, true)) => ("Built-in Display".to_string(), true),
+                    Some((_, false)) => (format!("External Display {}", n), false),
+                    None => (format!("Display {}", n + 1), false),
+                };
+                CaptureDisplay { index, device_name, label, builtin }
+            })
+            .collect())
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        // Other platforms capture the whole desktop without a device index
+        Ok(Vec::new())
+    }
+}
+
+async fn find_capturable_window(window_id: u32) -> Result<CapturableWindow, String> {
+    let window = query_capturable_windows()
+        .await?
+        .into_iter()
+        .find(|w| w.id == window_id)
+        .ok_or_else(|| format!("Window {} is not on screen", window_id))?;
+    // The crop uses the main display's origin and scale, so it would land in the wrong place
+    if !window.on_main_display {
+        return Err(format!(
+            "Window {} is not entirely on the main display; move it there to share it",
+            window_id
+        ));
+    }
+    Ok(window)
+}
+
+// Crop the full-display capture down to one window; yuv420p needs even dimensions
+#[cfg(target_os = "macos")]
+fn window_crop_filter(window: &CapturableWindow) -> String {
+    let even = |v: f64| ((v * window.scale).max(0.0) as u32) & !1;
+    format!(
+        "crop={}:{}:{}:{}",
+        even(window.width).max(2),
+        even(window.height).max(2),
+        even(window.x),
+        even(window.y)
+    )
+}
+
 // Get platform-specific FFmpeg input arguments
 fn get_ffmpeg_input_args(device: Option<&str>) -> Vec<String> {
     #[cfg(target_os = "macos")]
@@ -1260,55 +5465,422 @@ fn get_ffmpeg_input_args(device: Option<&str>) -> Vec<String> {
     }
 }
 
-// Cleanup HLS directory - remove all .ts and .m3u8 files
-fn cleanup_hls_directory(public_dir: &PathBuf) -> Result<(), String> {
-    eprintln!("🧹 Cleaning up HLS directory: {}", public_dir.display());
-    
-    if !public_dir.exists() {
-        eprintln!("  Directory doesn't exist, skipping cleanup");
-        return Ok(());
-    }
-    
-    let mut cleaned_count = 0;
-    match fs::read_dir(public_dir) {
-        Ok(entries) => {
-            for entry in entries {
-                match entry {
-                    Ok(entry) => {
-                        let path = entry.path();
-                        if path.is_file() {
-                            if let Some(ext) = path.extension() {
-                                if ext == "ts" || ext == "m3u8" {
-                                    match fs::remove_file(&path) {
-                                        Ok(_) => {
-                                            cleaned_count += 1;
-                                            eprintln!("  ✓ Removed: {}", path.file_name().unwrap_or_default().to_string_lossy());
-                                        }
-                                        Err(e) => {
-                                            eprintln!("  ⚠️  Failed to remove {}: {}", path.display(), e);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("  ⚠️  Error reading directory entry: {}", e);
-                    }
-                }
+// Cleanup HLS directory - remove all .ts, .m3u8, poster .jpg, preview .gif and leftover .tmp files
+fn cleanup_hls_directory(public_dir: &PathBuf) -> Result<(), String> {
+    info!("🧹 Cleaning up HLS directory: {}", public_dir.display());
+    
+    if !public_dir.exists() {
+        debug!("  Directory doesn't exist, skipping cleanup");
+        return Ok(());
+    }
+    
+    let mut cleaned_count = 0;
+    match fs::read_dir(public_dir) {
+        Ok(entries) => {
+            for entry in entries {
+                match entry {
+                    Ok(entry) => {
+                        let path = entry.path();
+                        if path.is_file() {
+                            if let Some(ext) = path.extension() {
+                                if ext == "ts" || ext == "m3u8" || ext == "jpg" || ext == "gif" || ext == "tmp" {
+                                    match fs::remove_file(&path) {
+                                        Ok(_) => {
+                                            cleaned_count += 1;
+                                            debug!("  ✓ Removed: {}", path.file_name().unwrap_or_default().to_string_lossy());
+                                        }
+                                        Err(e) => {
+                                            warn!("  ⚠️  Failed to remove {}: {}", path.display(), e);
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        warn!("  ⚠️  Error reading directory entry: {}", e);
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            return Err(format!("Failed to read HLS directory: {}", e));
+        }
+    }
+    
+    info!("✅ Cleanup complete: removed {} files", cleaned_count);
+    Ok(())
+}
+
+const SOFTWARE_VIDEO_ENCODER: &str = "libx264";
+
+// Hardware H.264 encoders worth trying on this platform, in order of preference
+fn hardware_encoder_candidates() -> &'static [&'static str] {
+    #[cfg(target_os = "macos")]
+    {
+        &["h264_videotoolbox"]
+    }
+    #[cfg(target_os = "windows")]
+    {
+        &["h264_nvenc", "h264_qsv", "h264_amf"]
+    }
+    #[cfg(target_os = "linux")]
+    {
+        &["h264_vaapi", "h264_nvenc", "h264_qsv"]
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        &[]
+    }
+}
+
+// List the video encoders this FFmpeg build was compiled with
+async fn probe_ffmpeg_encoders() -> Result<Vec<String>, String> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-encoders"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffmpeg -encoders: {}", e))?;
+
+    // Lines look like " V....D h264_nvenc           NVIDIA NVENC H.264 encoder"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let flags = parts.next()?;
+            let name = parts.next()?;
+            (flags.starts_with('V') && flags.len() == 6).then(|| name.to_string())
+        })
+        .collect())
+}
+
+// A test encode that hangs (e.g. a wedged GPU driver) counts as a failure
+const ENCODER_TEST_TIMEOUT_SECS: u64 = 10;
+
+// Whether the encoder works on this machine, not just in this build: stock FFmpeg builds
+// list nvenc/vaapi even without an NVIDIA GPU or a render node
+async fn encoder_works(encoder: &str) -> bool {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-hide_banner", "-loglevel", "error"]);
+    if encoder == "h264_vaapi" {
+        cmd.args(["-vaapi_device", "/dev/dri/renderD128"]);
+    }
+    cmd.args(["-f", "lavfi", "-i", "nullsrc=s=320x240", "-frames:v", "1"]);
+    if let Some(filter) = encoder_upload_filter(encoder) {
+        cmd.args(["-vf", filter]);
+    }
+    cmd.args(video_encoder_args(encoder))
+        .args(["-f", "null", "-"])
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true);
+
+    let output = tokio::time::timeout(
+        tokio::time::Duration::from_secs(ENCODER_TEST_TIMEOUT_SECS),
+        cmd.output(),
+    )
+    .await;
+    match output {
+        Ok(Ok(output)) if output.status.success() => true,
+        Ok(Ok(output)) => {
+            debug!("Encoder {} failed its test encode: {}", encoder, String::from_utf8_lossy(&output.stderr).trim());
+            false
+        }
+        _ => false,
+    }
+}
+
+// The first hardware candidate that passes a test encode, falling back to software
+async fn detect_auto_video_encoder() -> String {
+    let available = match probe_ffmpeg_encoders().await {
+        Ok(available) => available,
+        Err(e) => {
+            warn!("⚠️  Could not probe encoders, using {}: {}", SOFTWARE_VIDEO_ENCODER, e);
+            return SOFTWARE_VIDEO_ENCODER.to_string();
+        }
+    };
+    for candidate in hardware_encoder_candidates() {
+        if available.iter().any(|name| name == candidate) && encoder_works(candidate).await {
+            return candidate.to_string();
+        }
+    }
+    SOFTWARE_VIDEO_ENCODER.to_string()
+}
+
+// Hardware doesn't change while the app runs, so "auto" is only detected once
+static AUTO_VIDEO_ENCODER: tokio::sync::OnceCell<String> = tokio::sync::OnceCell::const_new();
+
+// Turn the encoder option into the codec FFmpeg should use
+async fn resolve_video_encoder(requested: Option<&str>) -> Result<String, String> {
+    match requested.unwrap_or("auto") {
+        "software" => Ok(SOFTWARE_VIDEO_ENCODER.to_string()),
+        "auto" => Ok(AUTO_VIDEO_ENCODER.get_or_init(detect_auto_video_encoder).await.clone()),
+        explicit => {
+            // The name ends up as an FFmpeg argument, so keep it to plain identifiers
+            if explicit.is_empty()
+                || !explicit.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                return Err(format!("Invalid encoder name: {}", explicit));
+            }
+            if let Ok(available) = probe_ffmpeg_encoders().await {
+                if !available.iter().any(|name| name == explicit) {
+                    return Err(format!("Encoder {} is not available in this FFmpeg build", explicit));
+                }
+            }
+            Ok(explicit.to_string())
+        }
+    }
+}
+
+// Codec-specific args; each encoder names its low-latency knobs differently
+fn video_encoder_args(encoder: &str) -> Vec<String> {
+    let args: &[&str] = match encoder {
+        "libx264" => &[
+            "-preset", "ultrafast",
+            "-tune", "zerolatency",
+            "-profile:v", "baseline",
+            "-level", "3.0",
+            "-pix_fmt", "yuv420p",
+        ],
+        "h264_videotoolbox" => &["-realtime", "1", "-b:v", "4M", "-pix_fmt", "yuv420p"],
+        "h264_nvenc" => &["-preset", "p1", "-tune", "ll", "-b:v", "4M", "-pix_fmt", "yuv420p"],
+        "h264_qsv" => &["-preset", "veryfast", "-b:v", "4M", "-pix_fmt", "nv12"],
+        "h264_vaapi" => &["-b:v", "4M"],
+        _ => &["-b:v", "4M", "-pix_fmt", "yuv420p"],
+    };
+
+    let mut result = vec!["-c:v".to_string(), encoder.to_string()];
+    result.extend(args.iter().map(|arg| arg.to_string()));
+    // Keyframe every 2s so each HLS segment starts cleanly
+    result.extend(["-g".to_string(), "60".to_string()]);
+    result
+}
+
+// Filter that has to run last in the video chain for this encoder
+fn encoder_upload_filter(encoder: &str) -> Option<&'static str> {
+    // VAAPI needs frames uploaded to the GPU surface first
+    (encoder == "h264_vaapi").then_some("format=nv12,hwupload")
+}
+
+// Renditions encoded in adaptive mode: (variant name, height, video bitrate)
+const HLS_RENDITIONS: &[(&str, u32, &str)] = &[("720p", 720, "3M"), ("480p", 480, "1200k")];
+const HLS_MASTER_PLAYLIST: &str = "master.m3u8";
+const HLS_STREAM_PLAYLIST: &str = "stream.m3u8";
+
+// Whether the capture input carries an audio stream to map into every rendition
+fn capture_has_audio(device: Option<&str>) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        device.and_then(|d| d.split(':').nth(1)) != Some("none")
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let _ = device;
+        true
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        let _ = device;
+        false
+    }
+}
+
+// Name fragments of virtual devices that carry system output back in as an input
+#[cfg(target_os = "macos")]
+const MACOS_LOOPBACK_DEVICE_HINTS: &[&str] = &["BlackHole", "Loopback", "Soundflower", "Aggregate"];
+#[cfg(target_os = "windows")]
+const WINDOWS_LOOPBACK_DEVICE_HINTS: &[&str] =
+    &["Stereo Mix", "virtual-audio-capturer", "What U Hear", "Wave Out Mix", "Loopback"];
+
+// Extra FFmpeg input capturing system audio. macOS needs a virtual loopback device
+// (e.g. BlackHole) with the output routed through it via a Multi-Output Device
+#[cfg(target_os = "macos")]
+async fn system_audio_input_args() -> Result<Vec<String>, String> {
+    let devices = list_ffmpeg_devices().await?;
+    let index = devices["audio"]
+        .as_array()
+        .and_then(|list| {
+            list.iter().find(|d| {
+                d["name"].as_str().is_some_and(|name| {
+                    MACOS_LOOPBACK_DEVICE_HINTS.iter().any(|hint| name.contains(hint))
+                })
+            })
+        })
+        .and_then(|d| d["index"].as_u64())
+        .ok_or_else(|| {
+            "System audio on macOS needs a virtual audio device such as BlackHole; install one and \
+             send output to a Multi-Output Device that includes it"
+                .to_string()
+        })?;
+    Ok(vec![
+        "-f".to_string(),
+        "avfoundation".to_string(),
+        "-i".to_string(),
+        format!(":{}", index), // Audio only
+    ])
+}
+
+// dshow has no loopback of its own; it relies on the driver's "Stereo Mix" style device
+// or a virtual capturer such as screen-capture-recorder
+#[cfg(target_os = "windows")]
+async fn system_audio_input_args() -> Result<Vec<String>, String> {
+    let output = Command::new("ffmpeg")
+        .args(["-f", "dshow", "-list_devices", "true", "-i", "dummy"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let device = stderr
+        .lines()
+        .filter(|line| line.contains("[dshow @") && !line.contains("Alternative"))
+        .filter_map(|line| line.split('"').nth(1))
+        .find(|name| WINDOWS_LOOPBACK_DEVICE_HINTS.iter().any(|hint| name.contains(hint)))
+        .ok_or_else(|| {
+            "No loopback audio device found; enable \"Stereo Mix\" in Sound settings or install \
+             a virtual audio capturer"
+                .to_string()
+        })?;
+    Ok(vec![
+        "-f".to_string(),
+        "dshow".to_string(),
+        "-i".to_string(),
+        format!("audio={}", device),
+    ])
+}
+
+// PulseAudio (and PipeWire's pulse server) expose every sink's output as "<sink>.monitor"
+#[cfg(target_os = "linux")]
+async fn system_audio_input_args() -> Result<Vec<String>, String> {
+    let output = Command::new("pactl")
+        .args(["get-default-sink"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run pactl: {}", e))?;
+    let sink = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || sink.is_empty() {
+        return Err("Could not find the default PulseAudio sink".to_string());
+    }
+    Ok(vec![
+        "-f".to_string(),
+        "pulse".to_string(),
+        "-i".to_string(),
+        format!("{}.monitor", sink),
+    ])
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+async fn system_audio_input_args() -> Result<Vec<String>, String> {
+    Err("System audio capture is not supported on this platform".to_string())
+}
+
+// Split the captured video into one scaled stream per rendition, labelled [v0], [v1], ...
+fn rendition_filter_graph(prefilter: Option<&str>, upload: Option<&str>) -> String {
+    let mut graph = String::from("[0:v]");
+    if let Some(prefilter) = prefilter {
+        graph.push_str(prefilter);
+        graph.push(',');
+    }
+    graph.push_str(&format!("split={}", HLS_RENDITIONS.len()));
+    for i in 0..HLS_RENDITIONS.len() {
+        graph.push_str(&format!("[s{}]", i));
+    }
+    for (i, (_, height, _)) in HLS_RENDITIONS.iter().enumerate() {
+        graph.push_str(&format!(";[s{}]scale=-2:{}", i, height));
+        if let Some(upload) = upload {
+            graph.push(',');
+            graph.push_str(upload);
+        }
+        graph.push_str(&format!("[v{}]", i));
+    }
+    graph
+}
+
+// Output filters and stream mapping. These are output options, so they go after every input
+fn ffmpeg_output_map_args(
+    adaptive: bool,
+    crop: Option<&str>,
+    upload: Option<&str>,
+    capture_audio: Option<&str>,
+    system_audio: Option<&str>,
+) -> Vec<String> {
+    let mut args = Vec::new();
+    // A filter graph label can only be consumed once, so adaptive mode splits the mix per rendition
+    let audio_outputs = if adaptive { HLS_RENDITIONS.len() } else { 1 };
+    let (audio_mix, audio_inputs) = match (capture_audio, system_audio) {
+        (Some(capture_audio), Some(system_audio)) => {
+            let mut mix = format!("[{}][{}]amix=inputs=2:duration=longest", capture_audio, system_audio);
+            let labels: Vec<String> = if audio_outputs > 1 {
+                mix.push_str(&format!(",asplit={}", audio_outputs));
+                (0..audio_outputs).map(|i| format!("[aout{}]", i)).collect()
+            } else {
+                vec!["[aout]".to_string()]
+            };
+            mix.push_str(&labels.concat());
+            (Some(mix), labels)
+        }
+        (capture_audio, system_audio) => {
+            let input = system_audio.or(capture_audio);
+            (None, input.map(|i| vec![i.to_string(); audio_outputs]).unwrap_or_default())
+        }
+    };
+
+    if adaptive {
+        let mut graph = rendition_filter_graph(crop, upload);
+        if let Some(audio_mix) = &audio_mix {
+            graph.push(';');
+            graph.push_str(audio_mix);
+        }
+        args.extend(["-filter_complex".to_string(), graph]);
+
+        let mut stream_map = Vec::new();
+        for (i, (name, _, bitrate)) in HLS_RENDITIONS.iter().enumerate() {
+            args.extend(["-map".to_string(), format!("[v{}]", i)]);
+            args.extend([format!("-b:v:{}", i), bitrate.to_string()]);
+            if let Some(audio_input) = audio_inputs.get(i) {
+                args.extend(["-map".to_string(), audio_input.clone()]);
+                stream_map.push(format!("v:{},a:{},name:{}", i, i, name));
+            } else {
+                stream_map.push(format!("v:{},name:{}", i, name));
             }
         }
-        Err(e) => {
-            return Err(format!("Failed to read HLS directory: {}", e));
+        args.extend([
+            "-master_pl_name".to_string(),
+            HLS_MASTER_PLAYLIST.to_string(),
+            "-var_stream_map".to_string(),
+            stream_map.join(" "),
+        ]);
+    } else {
+        let video_filters: Vec<&str> = crop.into_iter().chain(upload).collect();
+        if !video_filters.is_empty() {
+            args.extend(["-vf".to_string(), video_filters.join(",")]);
+        }
+        if let Some(audio_mix) = audio_mix {
+            args.extend(["-filter_complex".to_string(), audio_mix]);
+        }
+        // Video always comes from the first input; audio from wherever it was resolved above
+        args.extend(["-map".to_string(), "0:v".to_string()]);
+        if let Some(audio_input) = audio_inputs.first() {
+            args.extend(["-map".to_string(), audio_input.clone()]);
         }
     }
-    
-    eprintln!("✅ Cleanup complete: removed {} files", cleaned_count);
-    Ok(())
+    args
 }
 
 // Start FFmpeg process
-async fn start_ffmpeg(public_dir: &PathBuf, device: Option<&str>) -> anyhow::Result<tokio::process::Child> {
+async fn start_ffmpeg(
+    public_dir: &PathBuf,
+    device: Option<&str>,
+    encoder: &str,
+    window: Option<&CapturableWindow>,
+    options: &HlsStartOptions,
+    segment_prefix: &str,
+) -> anyhow::Result<tokio::process::Child> {
+    let adaptive = options.adaptive.unwrap_or(false);
+    let system_audio = match options.include_system_audio {
+        Some(true) => Some(system_audio_input_args().await.map_err(|e| anyhow::anyhow!(e))?),
+        _ => None,
+    };
+
     // Clean up old files first
     cleanup_hls_directory(public_dir).map_err(|e| anyhow::anyhow!("Cleanup failed: {}", e))?;
     
@@ -1325,6 +5897,11 @@ async fn start_ffmpeg(public_dir: &PathBuf, device: Option<&str>) -> anyhow::Res
         "-analyzeduration".to_string(),
         "50M".to_string(),
     ];
+
+    if encoder == "h264_vaapi" {
+        // Global option, so it has to come before the inputs
+        args.extend(["-vaapi_device".to_string(), "/dev/dri/renderD128".to_string()]);
+    }
     
     #[cfg(target_os = "windows")]
     {
@@ -1341,7 +5918,7 @@ async fn start_ffmpeg(public_dir: &PathBuf, device: Option<&str>) -> anyhow::Res
         // Get audio device name and add audio input
         // Try wasapi first (if supported), fallback to dshow
         if let Some(audio_device_name) = get_windows_audio_device_name(audio_index).await {
-            eprintln!("🔊 Using audio device: \"{}\" (index: {})", audio_device_name, audio_index);
+            info!("🔊 Using audio device: \"{}\" (index: {})", audio_device_name, audio_index);
             
             // Check if wasapi is actually supported
             let wasapi_test = Command::new("ffmpeg")
@@ -1375,12 +5952,13 @@ async fn start_ffmpeg(public_dir: &PathBuf, device: Option<&str>) -> anyhow::Res
                         "-f".to_string(),
                         "dshow".to_string(),
                         "-i".to_string(),
-                        format!("audio=\"{}\"", audio_device_name),
+                        // Passed as a single argv entry (no shell), so no quoting is needed
+                        format!("audio={}", audio_device_name),
                     ]);
                 }
             }
         } else {
-            eprintln!("⚠️  Could not find audio device at index {}, using default", audio_index);
+            warn!("⚠️  Could not find audio device at index {}, using default", audio_index);
             // Use dshow default (wasapi likely not available)
             args.extend(vec![
                 "-f".to_string(),
@@ -1390,13 +5968,6 @@ async fn start_ffmpeg(public_dir: &PathBuf, device: Option<&str>) -> anyhow::Res
             ]);
         }
         
-        // Add stream mapping for Windows (map video from input 0, audio from input 1)
-        args.extend(vec![
-            "-map".to_string(),
-            "0:v".to_string(), // Video from first input (gdigrab)
-            "-map".to_string(),
-            "1:a".to_string(), // Audio from second input (dshow)
-        ]);
     }
     
     #[cfg(not(target_os = "windows"))]
@@ -1404,21 +5975,35 @@ async fn start_ffmpeg(public_dir: &PathBuf, device: Option<&str>) -> anyhow::Res
         // Add platform-specific input (macOS/Linux use single input)
         args.extend(get_ffmpeg_input_args(device));
     }
-    
+
+    // Windows takes audio from its second input (dshow/wasapi), macOS from the capture device
+    let capture_audio = capture_has_audio(device)
+        .then_some(if cfg!(target_os = "windows") { "1:a" } else { "0:a" });
+    // System audio comes after the capture inputs and is mixed with the microphone if there is one
+    let system_audio_input = system_audio.map(|system_audio| {
+        args.extend(system_audio);
+        if cfg!(target_os = "windows") { "2:a" } else { "1:a" }
+    });
+
+    // avfoundation can't target a window, so capture the display and crop to it
+    #[cfg(target_os = "macos")]
+    let crop = window.map(window_crop_filter);
+    #[cfg(not(target_os = "macos"))]
+    let crop: Option<String> = {
+        let _ = window; // Window capture is macOS only
+        None
+    };
+
     // Add encoding and output args
+    args.extend(video_encoder_args(encoder));
+    args.extend(ffmpeg_output_map_args(
+        adaptive,
+        crop.as_deref(),
+        encoder_upload_filter(encoder),
+        capture_audio,
+        system_audio_input,
+    ));
     args.extend(vec![
-        "-c:v".to_string(),
-        "libx264".to_string(),
-        "-preset".to_string(),
-        "ultrafast".to_string(),
-        "-tune".to_string(),
-        "zerolatency".to_string(),
-        "-profile:v".to_string(),
-        "baseline".to_string(),
-        "-level".to_string(),
-        "3.0".to_string(),
-        "-pix_fmt".to_string(),
-        "yuv420p".to_string(),
         "-c:a".to_string(),
         "aac".to_string(),
         "-ar".to_string(),
@@ -1437,12 +6022,24 @@ async fn start_ffmpeg(public_dir: &PathBuf, device: Option<&str>) -> anyhow::Res
         "delete_segments+independent_segments".to_string(),
         "-hls_segment_type".to_string(),
         "mpegts".to_string(),
-        "-hls_segment_filename".to_string(),
-        format!("{}/segment_%03d.ts", public_dir.display()),
-        format!("{}/stream.m3u8", public_dir.display()),
     ]);
+    args.extend(options.extra_ffmpeg_args.iter().cloned());
+    if adaptive {
+        // %v expands to the variant name from -var_stream_map
+        args.extend([
+            "-hls_segment_filename".to_string(),
+            format!("{}/{}_%v_%06d.ts", public_dir.display(), segment_prefix),
+            format!("{}/stream_%v.m3u8", public_dir.display()),
+        ]);
+    } else {
+        args.extend([
+            "-hls_segment_filename".to_string(),
+            format!("{}/{}_%06d.ts", public_dir.display(), segment_prefix),
+            format!("{}/{}", public_dir.display(), HLS_STREAM_PLAYLIST),
+        ]);
+    }
     
-    eprintln!("🎬 FFmpeg command: ffmpeg {}", args.join(" "));
+    info!("🎬 FFmpeg command: ffmpeg {}", args.join(" "));
     
     let mut cmd = Command::new("ffmpeg");
     cmd.args(&args);
@@ -1453,287 +6050,848 @@ async fn start_ffmpeg(public_dir: &PathBuf, device: Option<&str>) -> anyhow::Res
     Ok(child)
 }
 
-// HTTP handler for API info
-async fn hls_api_info(State(state): State<Arc<HlsServerState>>) -> axum::Json<serde_json::Value> {
-    axum::Json(serde_json::json!({
-        "code": state.access_code,
-        "port": state.port,
-    }))
+// How long start_hls_server_cmd watches FFmpeg before reporting success
+const FFMPEG_STARTUP_CHECK_SECS: u64 = 5;
+const FFMPEG_LOG_LINES: usize = 200;
+
+// Stderr lines FFmpeg only prints when it's giving up. Bare errno text like "Invalid argument"
+// or "Permission denied" also shows up in per-frame warnings of healthy streams, so those
+// failures are caught by FFmpeg exiting instead
+const FFMPEG_FATAL_PATTERNS: &[&str] = &[
+    "Error opening input",
+    "Error opening output",
+    "Unknown input format",
+    "Unknown encoder",
+    "Cannot open display",
+    "Error while opening encoder",
+    "Error initializing output stream",
+    "Conversion failed!",
+];
+
+fn is_fatal_ffmpeg_line(line: &str) -> bool {
+    FFMPEG_FATAL_PATTERNS.iter().any(|pattern| line.contains(pattern))
+}
+
+// Pull "1280x720 @ 30 fps" out of an input stream line like
+// "Stream #0:0: Video: rawvideo (UYVY / 0x59565955), uyvy422, 1280x720, 30 tbr, ..."
+fn parse_stream_resolution(line: &str) -> Option<String> {
+    let video = &line[line.find("Video:")?..];
+    let fields: Vec<&str> = video.split(',').map(|f| f.trim()).collect();
+
+    let resolution = fields.iter().find_map(|field| {
+        let size = field.split_whitespace().next()?;
+        let (w, h) = size.split_once('x')?;
+        (w.parse::<u32>().is_ok() && h.parse::<u32>().is_ok()).then(|| size.to_string())
+    })?;
+    let fps = fields.iter().find_map(|field| {
+        field
+            .strip_suffix(" fps")
+            .or_else(|| field.strip_suffix(" tbr"))
+            .filter(|rate| rate.parse::<f64>().is_ok())
+    });
+
+    Some(match fps {
+        Some(fps) => format!("{} @ {} fps", resolution, fps),
+        None => resolution,
+    })
+}
+
+// Keep the most recent FFmpeg stderr lines around for diagnostics
+fn spawn_ffmpeg_log_reader(
+    stderr: tokio::process::ChildStderr,
+    log: Arc<Mutex<std::collections::VecDeque<String>>>,
+    actual_resolution: Arc<Mutex<Option<String>>>,
+) {
+    tokio::spawn(async move {
+        use tokio::io::AsyncReadExt;
+
+        let mut stderr = stderr;
+        let mut buf = [0u8; 4096];
+        let mut pending = Vec::new();
+        // Stream lines after "Output #0" describe the encoded output, not the device
+        let mut in_output_section = false;
+        loop {
+            let n = match stderr.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            pending.extend_from_slice(&buf[..n]);
+
+            // Progress updates end in \r rather than \n, so split on both
+            while let Some(pos) = pending.iter().position(|&b| b == b'\n' || b == b'\r') {
+                let line = String::from_utf8_lossy(&pending[..pos]).trim().to_string();
+                pending.drain(..=pos);
+                if line.is_empty() {
+                    continue;
+                }
+                if is_fatal_ffmpeg_line(&line) {
+                    error!("❌ FFmpeg: {}", line);
+                }
+                if line.starts_with("Output #") {
+                    in_output_section = true;
+                } else if !in_output_section && line.starts_with("Stream #") {
+                    if let Some(resolution) = parse_stream_resolution(&line) {
+                        if let Ok(mut actual) = actual_resolution.lock() {
+                            if actual.is_none() {
+                                info!("🖥️  Capture negotiated at {}", resolution);
+                                *actual = Some(resolution);
+                            }
+                        }
+                    }
+                }
+                if let Ok(mut log) = log.lock() {
+                    if log.len() >= FFMPEG_LOG_LINES {
+                        log.pop_front();
+                    }
+                    log.push_back(line);
+                }
+            }
+        }
+    });
+}
+
+// Wait until FFmpeg writes its first playlist, exits, or logs a fatal error
+async fn wait_for_ffmpeg_startup(
+    child: &mut tokio::process::Child,
+    playlist: &Path,
+    log: &Arc<Mutex<std::collections::VecDeque<String>>>,
+) -> Result<(), String> {
+    let deadline = tokio::time::Instant::now() + tokio::time::Duration::from_secs(FFMPEG_STARTUP_CHECK_SECS);
+
+    while tokio::time::Instant::now() < deadline {
+        // The line before the fatal one usually names the cause
+        let fatal = log.lock().ok().and_then(|log| {
+            let index = log.iter().position(|line| is_fatal_ffmpeg_line(line))?;
+            Some(log.range(index.saturating_sub(1)..=index).cloned().collect::<Vec<_>>().join(" / "))
+        });
+        if let Some(lines) = fatal {
+            return Err(format!("FFmpeg failed to start capture: {}", lines));
+        }
+
+        if let Ok(Some(status)) = child.try_wait() {
+            // The cause usually comes just before a generic closing line like "Conversion failed!"
+            let last_lines = log
+                .lock()
+                .ok()
+                .map(|log| log.iter().rev().take(2).rev().cloned().collect::<Vec<_>>().join(" / "))
+                .filter(|lines| !lines.is_empty());
+            return Err(format!(
+                "FFmpeg exited during startup ({}): {}",
+                status,
+                last_lines.unwrap_or_else(|| "no output".to_string())
+            ));
+        }
+
+        if playlist.exists() {
+            return Ok(());
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    }
+
+    // Slow devices can take longer than the check window; keep going and let the log tell
+    warn!("⚠️  FFmpeg hasn't written a playlist after {}s, continuing", FFMPEG_STARTUP_CHECK_SECS);
+    Ok(())
+}
+
+// Parse an allowed_cidrs entry; a bare address is a single-host network
+fn parse_cidr(cidr: &str) -> Result<(std::net::IpAddr, u8), String> {
+    let invalid = || format!("Invalid CIDR '{}': use an address or address/prefix", cidr);
+    let cidr = cidr.trim();
+    let (addr, prefix) = match cidr.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (cidr, None),
+    };
+    let addr: std::net::IpAddr = addr.parse().map_err(|_| invalid())?;
+    let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|prefix| *prefix <= max_prefix)
+            .ok_or_else(invalid)?,
+        None => max_prefix,
+    };
+    Ok((addr, prefix))
+}
+
+fn cidr_contains(&(network, prefix): &(std::net::IpAddr, u8), ip: std::net::IpAddr) -> bool {
+    use std::net::IpAddr;
+    match (network, ip.to_canonical()) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+// Address checked against allowed_cidrs. The server only listens on loopback, so a request
+// without x-forwarded-for came from this machine. Through the tunnel the real client is the
+// entry the tunnel appended last; anything before it was supplied by the client and can be forged
+fn access_client_ip(headers: &axum::http::HeaderMap) -> Option<std::net::IpAddr> {
+    match headers.get_all("x-forwarded-for").iter().next_back() {
+        Some(forwarded) => forwarded
+            .to_str()
+            .ok()?
+            .rsplit(',')
+            .next()
+            .and_then(|ip| ip.trim().parse().ok()),
+        None => Some(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST)),
+    }
+}
+
+fn is_allowed_client(state: &HlsServerState, headers: &axum::http::HeaderMap) -> bool {
+    if state.allowed_cidrs.is_empty() {
+        return true;
+    }
+    let ip = access_client_ip(headers);
+    let allowed = ip.is_some_and(|ip| state.allowed_cidrs.iter().any(|cidr| cidr_contains(cidr, ip)));
+    if !allowed {
+        debug!("🚫 Blocked viewer outside allowed_cidrs: {:?}", ip);
+    }
+    allowed
 }
 
+// Append the access code to every URI line of a playlist so segment requests are authorized
+fn rewrite_playlist_with_code(playlist: &str, code: &str) -> String {
+    let mut rewritten = String::with_capacity(playlist.len() + 64);
+    for line in playlist.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            rewritten.push_str(line);
+        } else {
+            let separator = if trimmed.contains('?') { '&' } else { '?' };
+            rewritten.push_str(&format!("{}{}code={}", trimmed, separator, code));
+        }
+        rewritten.push('\n');
+    }
+    rewritten
+}
 
-// Serve HLS segment files with auth
-async fn serve_hls_file(
-    path: axum::extract::Path<String>,
+// HTTP handler for API info. The code is only echoed to callers that already sent it,
+// otherwise a one-time token viewer could read the reusable code from here
+async fn hls_api_info(
     State(state): State<Arc<HlsServerState>>,
     headers: axum::http::HeaderMap,
     query: axum::extract::Query<std::collections::HashMap<String, String>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let path_str = path.as_str();
-    eprintln!("📦 Request for segment: {}", path_str);
-    
-    // Validate access code for segment files
+) -> Result<axum::Json<serde_json::Value>, StatusCode> {
+    if !is_allowed_client(&state, &headers) {
+        return Err(StatusCode::FORBIDDEN);
+    }
     let provided_code = headers
         .get("x-access-code")
         .and_then(|h| h.to_str().ok())
         .or_else(|| query.get("code").map(|s| s.as_str()));
-    
-    if let Some(code) = provided_code {
-        if code != state.access_code {
-            eprintln!("❌ Invalid access code for segment: {}", path_str);
-            return Err(StatusCode::FORBIDDEN);
-        }
-    } else {
-        eprintln!("❌ No access code provided for segment: {}", path_str);
+    Ok(axum::Json(serde_json::json!({
+        "code": (provided_code == Some(state.access_code.as_str())).then_some(&state.access_code),
+        "port": state.port,
+        "ended": state.ended.load(std::sync::atomic::Ordering::SeqCst),
+        "paused": state.paused.load(std::sync::atomic::Ordering::SeqCst),
+        "title": state.stream_title,
+        "poster": "/poster.jpg",
+        "preview": state.preview_gif.then_some("/preview.gif"),
+    })))
+}
+
+const POSTER_FILE_NAME: &str = "poster.jpg";
+const POSTER_REFRESH_SECS: u64 = 10;
+
+// Built-in player at /, opened as /?code=... It shows the poster until the stream starts.
+// Safari plays HLS natively; other browsers load hls.js
+const HLS_VIEWER_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>PathFinder stream</title>
+<style>
+  body { margin: 0; background: #000; color: #ccc; font-family: sans-serif; }
+  video { width: 100vw; height: 100vh; object-fit: contain; }
+</style>
+</head>
+<body>
+<video id="player" controls autoplay muted playsinline></video>
+<script>
+  const params = new URLSearchParams(location.search);
+  const auth = params.has("code") ? "code=" + encodeURIComponent(params.get("code"))
+    : params.has("token") ? "token=" + encodeURIComponent(params.get("token")) : "";
+  const video = document.getElementById("player");
+  const src = "/stream.m3u8?" + auth;
+  fetch("/api/info?" + auth).then((r) => r.json()).then((info) => {
+    if (info.title) document.title = info.title;
+    // Token viewers only get a session cookie once the playlist is fetched
+    if (params.has("code")) video.poster = info.poster + "?" + auth;
+  }).catch(() => {});
+  if (video.canPlayType("application/vnd.apple.mpegurl")) {
+    video.src = src;
+  } else {
+    const script = document.createElement("script");
+    script.src = "https://cdn.jsdelivr.net/npm/hls.js@1";
+    script.onload = () => {
+      const hls = new Hls();
+      hls.loadSource(src);
+      hls.attachMedia(video);
+    };
+    document.head.appendChild(script);
+  }
+</script>
+</body>
+</html>
+"#;
+
+// The viewer page carries no stream data, so it's served without the code like /api/info
+async fn serve_hls_viewer_page(
+    State(state): State<Arc<HlsServerState>>,
+    headers: axum::http::HeaderMap,
+) -> Result<axum::response::Html<&'static str>, StatusCode> {
+    if !is_allowed_client(&state, &headers) {
         return Err(StatusCode::FORBIDDEN);
     }
-    
-    // Construct full filename (path is like "012.ts" from route "/segment_:path")
-    let filename = format!("segment_{}", path_str);
-    let file_path = state.public_dir.join(&filename);
-    
-    eprintln!("📁 Looking for file: {}", file_path.display());
-    eprintln!("📁 Public dir: {}", state.public_dir.display());
-    
-    if file_path.exists() {
-        eprintln!("✅ Found segment file: {}", filename);
-        let content = fs::read(&file_path).map_err(|e| {
-            eprintln!("❌ Error reading file: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-        let content_type = "video/mp2t";
-        
-        Ok((
-            StatusCode::OK,
-            [(axum::http::header::CONTENT_TYPE, content_type)],
-            content,
-        ))
-    } else {
-        eprintln!("❌ Segment file not found: {}", filename);
-        // List files in directory for debugging
-        if let Ok(entries) = fs::read_dir(&state.public_dir) {
-            eprintln!("📂 Files in public dir:");
-            for entry in entries.flatten() {
-                if let Ok(name) = entry.file_name().into_string() {
-                    eprintln!("  - {}", name);
-                }
-            }
+    Ok(axum::response::Html(HLS_VIEWER_PAGE))
+}
+
+// Per-session segment name prefix, so a stale segment left by an earlier session is never served
+fn new_segment_prefix() -> String {
+    format!("seg{}", &uuid::Uuid::new_v4().simple().to_string()[..8])
+}
+
+fn is_session_segment(path: &Path, segment_prefix: &str) -> bool {
+    path.extension().is_some_and(|ext| ext == "ts")
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(segment_prefix))
+            .is_some_and(|rest| rest.starts_with('_'))
+}
+
+// Most recently written segment of this session in the public dir
+fn latest_hls_segment(public_dir: &PathBuf, segment_prefix: &str) -> Option<PathBuf> {
+    fs::read_dir(public_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_session_segment(path, segment_prefix))
+        .filter_map(|path| {
+            let modified = path.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, path))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+// Extract one JPEG frame from the latest segment so joining viewers get an immediate preview
+async fn generate_poster_frame(public_dir: &PathBuf, segment_prefix: &str) -> Result<(), String> {
+    let segment = latest_hls_segment(public_dir, segment_prefix).ok_or("No segments written yet")?;
+
+    // Write to a temp file and rename so viewers never fetch a half-written JPEG. The temp name
+    // has no servable extension, so the format is given explicitly
+    let tmp_path = public_dir.join(format!("{}.tmp", POSTER_FILE_NAME));
+    let output = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(&segment)
+        .args(["-frames:v", "1", "-q:v", "5", "-f", "mjpeg"])
+        .arg(&tmp_path)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    fs::rename(&tmp_path, public_dir.join(POSTER_FILE_NAME)).map_err(|e| e.to_string())
+}
+
+const PREVIEW_GIF_FILE_NAME: &str = "preview.gif";
+const PREVIEW_GIF_CACHE_SECS: u64 = 10;
+const PREVIEW_GIF_SEGMENTS: usize = 2;
+
+// The newest `count` segments of the rendition that wrote last, oldest first
+fn recent_hls_segments(public_dir: &PathBuf, segment_prefix: &str, count: usize) -> Vec<PathBuf> {
+    let Some(latest) = latest_hls_segment(public_dir, segment_prefix) else {
+        return Vec::new();
+    };
+    // "segabc_720p_000012.ts" -> "segabc_720p_", so adaptive renditions aren't mixed
+    let stem = latest.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let prefix = stem.rsplit_once('_').map(|(prefix, _)| format!("{}_", prefix)).unwrap_or_default();
+
+    let mut segments: Vec<(SystemTime, PathBuf)> = fs::read_dir(public_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "ts"))
+                .filter(|path| {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    name.strip_prefix(prefix.as_str()).is_some_and(|rest| !rest.contains('_'))
+                })
+                .filter_map(|path| Some((path.metadata().and_then(|m| m.modified()).ok()?, path)))
+                .collect()
+        })
+        .unwrap_or_default();
+    segments.sort_by_key(|(modified, _)| *modified);
+    let skip = segments.len().saturating_sub(count);
+    segments.into_iter().skip(skip).map(|(_, path)| path).collect()
+}
+
+// Transcode the last few seconds into a small looping GIF, reusing a recent one if fresh enough
+async fn generate_preview_gif(public_dir: &PathBuf, segment_prefix: &str) -> Result<PathBuf, String> {
+    let gif_path = public_dir.join(PREVIEW_GIF_FILE_NAME);
+    let fresh = gif_path
+        .metadata()
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age.as_secs() < PREVIEW_GIF_CACHE_SECS);
+    if fresh {
+        return Ok(gif_path);
+    }
+
+    let segments = recent_hls_segments(public_dir, segment_prefix, PREVIEW_GIF_SEGMENTS);
+    if segments.is_empty() {
+        return Err("No segments written yet".to_string());
+    }
+    let input = format!(
+        "concat:{}",
+        segments.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>().join("|")
+    );
+
+    let tmp_path = public_dir.join("preview_tmp.gif");
+    let output = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-i", &input])
+        .args(["-vf", "fps=8,scale=480:-1:flags=lanczos", "-loop", "0"])
+        .arg(&tmp_path)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    fs::rename(&tmp_path, &gif_path).map_err(|e| e.to_string())?;
+    Ok(gif_path)
+}
+
+// Aborts background tasks when the server future is dropped (e.g. the server task is aborted)
+struct AbortOnDrop(Vec<tokio::task::JoinHandle<()>>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
         }
-        Err(StatusCode::NOT_FOUND)
     }
 }
 
+
 // Start HLS server
-async fn start_hls_server(state: Arc<HlsServerState>) -> anyhow::Result<()> {
+async fn start_hls_server(state: Arc<HlsServerState>, listener: tokio::net::TcpListener) -> anyhow::Result<()> {
     use axum::routing::get;
     
     // Helper to get client IP
     fn get_client_ip(headers: &axum::http::HeaderMap) -> String {
-        // Try to get IP from X-Forwarded-For (for tunnel) or X-Real-IP
-        if let Some(forwarded) = headers.get("x-forwarded-for") {
-            if let Ok(forwarded_str) = forwarded.to_str() {
-                // Take the first IP if there are multiple
-                if let Some(ip) = forwarded_str.split(',').next() {
-                    return ip.trim().to_string();
-                }
-            }
-        }
-        if let Some(real_ip) = headers.get("x-real-ip") {
-            if let Ok(ip_str) = real_ip.to_str() {
-                return ip_str.to_string();
-            }
-        }
-        // Fallback to "unknown"
-        "unknown".to_string()
+        // Same address allowed_cidrs checks; earlier x-forwarded-for entries are client-supplied
+        access_client_ip(headers)
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown".to_string())
     }
     
+    // Helper to read the viewer session token from the Cookie header
+    fn get_viewer_session(headers: &axum::http::HeaderMap) -> Option<String> {
+        headers
+            .get_all(axum::http::header::COOKIE)
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .flat_map(|cookies| cookies.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(name, _)| *name == VIEWER_SESSION_COOKIE)
+            .map(|(_, value)| value.to_string())
+            .filter(|value| !value.is_empty())
+    }
+
     // Helper to track viewer
-    // For better tracking, we use IP + User-Agent as a unique identifier
-    // This helps distinguish multiple clients behind the same tunnel
-    fn track_viewer(state: &Arc<HlsServerState>, ip: String, user_agent: Option<&str>) {
+    // Viewers with a session cookie are tracked by that token. Clients that don't
+    // keep cookies fall back to IP + User-Agent, which still helps distinguish
+    // multiple clients behind the same tunnel
+    fn track_viewer(
+        state: &Arc<HlsServerState>,
+        ip: String,
+        user_agent: Option<&str>,
+        session: Option<&str>,
+        issued_session: Option<&str>,
+    ) {
         let mut viewers = state.viewers.lock().unwrap();
         
-        // Create a unique viewer ID from IP and User-Agent
-        let viewer_id = if let Some(ua) = user_agent {
+        let fallback_id = if let Some(ua) = user_agent {
             format!("{}|{}", ip, ua)
         } else {
             ip.clone()
         };
+        let viewer_id = match session {
+            Some(token) => {
+                // The request that issued the cookie was counted under the fallback id. Another
+                // client behind the same tunnel IP and User-Agent may share that entry, so it's
+                // only dropped if this session's first request was the last to touch it
+                if viewers
+                    .get(&fallback_id)
+                    .is_some_and(|viewer| viewer.issued_session.as_deref() == Some(token))
+                {
+                    viewers.remove(&fallback_id);
+                }
+                format!("session:{}", token)
+            }
+            None => fallback_id,
+        };
         
         let was_new = !viewers.contains_key(&viewer_id);
-        viewers.insert(viewer_id.clone(), SystemTime::now());
+        viewers.insert(viewer_id, ViewerInfo {
+            ip: ip.clone(),
+            last_seen: SystemTime::now(),
+            issued_session: issued_session.map(str::to_string),
+        });
         let count = viewers.len();
         
         if was_new {
-            eprintln!("👥 New viewer connected: {} (Total: {})", ip, count);
+            debug!("👥 New viewer connected: {} (Total: {})", ip, count);
         }
     }
     
-    // Handler for stream.m3u8 (no path param)
+    // Helper to validate the access code from the header or ?code= query param
+    fn has_valid_code(
+        state: &Arc<HlsServerState>,
+        headers: &axum::http::HeaderMap,
+        query: &std::collections::HashMap<String, String>,
+    ) -> bool {
+        let provided_code = headers
+            .get("x-access-code")
+            .and_then(|h| h.to_str().ok())
+            .or_else(|| query.get("code").map(|s| s.as_str()));
+
+        provided_code == Some(state.access_code.as_str())
+    }
+
+    // Viewers let in by a one-time token carry a session cookie instead of the code
+    fn has_token_session(state: &Arc<HlsServerState>, session: Option<&str>) -> bool {
+        session.is_some_and(|session| state.token_sessions.lock().unwrap().contains(session))
+    }
+
+    // Content type for the files FFmpeg writes into the public dir
+    fn hls_content_type(path: &str) -> Option<&'static str> {
+        let extension = path.rsplit('.').next().unwrap_or("");
+        match extension {
+            "m3u8" => Some("application/vnd.apple.mpegurl"),
+            "ts" => Some("video/mp2t"),
+            "m4s" | "mp4" => Some("video/mp4"), // fMP4 init/media segments
+            "jpg" => Some("image/jpeg"), // Poster frame
+            _ => None,
+        }
+    }
+
+    // Single handler for every HLS file (playlists and segments)
+    async fn serve_hls_path(
+        state: &Arc<HlsServerState>,
+        path: &str,
+        headers: &axum::http::HeaderMap,
+        query: &std::collections::HashMap<String, String>,
+    ) -> Result<Response, StatusCode> {
+        if state.verbose_logging {
+            info!("📦 Request for: {}", path);
+        }
+
+        // Only serve flat HLS files from the public dir
+        let content_type = match hls_content_type(path) {
+            Some(content_type) if !path.contains('/') && !path.contains("..") => content_type,
+            _ => return Err(StatusCode::NOT_FOUND),
+        };
+
+        if !is_allowed_client(state, headers) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        let session = get_viewer_session(headers);
+        let by_code = has_valid_code(state, headers, query);
+        // Cookie to set on this response: a fresh session on the first playlist fetch
+        let mut new_session = (session.is_none() && path.ends_with(".m3u8"))
+            .then(|| uuid::Uuid::new_v4().simple().to_string());
+        if !by_code && !has_token_session(state, session.as_deref()) {
+            // A one-time token opens the playlist once; the session cookie covers everything after
+            let redeemed = path.ends_with(".m3u8")
+                && query
+                    .get("token")
+                    .is_some_and(|token| state.viewer_tokens.lock().unwrap().remove(token));
+            if !redeemed {
+                error!("❌ Invalid or missing access code for: {}", path);
+                return Err(StatusCode::FORBIDDEN);
+            }
+            let admitted = match session.as_ref() {
+                Some(session) => session.clone(),
+                None => new_session
+                    .get_or_insert_with(|| uuid::Uuid::new_v4().simple().to_string())
+                    .clone(),
+            };
+            info!("🎟️  One-time viewer token redeemed");
+            state.token_sessions.lock().unwrap().insert(admitted);
+        }
+
+        // Segments from another session may linger if cleanup failed; never serve those
+        if content_type == "video/mp2t" && !is_session_segment(Path::new(path), &state.segment_prefix) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+
+        // Track viewer (update timestamp to keep them active). Poster fetches don't count
+        if content_type != "image/jpeg" {
+            let client_ip = get_client_ip(headers);
+            let user_agent = headers.get("user-agent")
+                .and_then(|h| h.to_str().ok());
+            track_viewer(state, client_ip, user_agent, session.as_deref(), new_session.as_deref());
+        }
+
+        let file_path = state.public_dir.join(path);
+        if state.verbose_logging {
+            info!("📁 Looking for file: {}", file_path.display());
+        }
+
+        if !file_path.exists() {
+            error!("❌ File not found: {}", path);
+            // List files in directory for debugging
+            if state.verbose_logging {
+                if let Ok(entries) = fs::read_dir(&state.public_dir) {
+                    info!("📂 Files in public dir:");
+                    for entry in entries.flatten() {
+                        if let Ok(name) = entry.file_name().into_string() {
+                            info!("  - {}", name);
+                        }
+                    }
+                }
+            }
+            return Err(StatusCode::NOT_FOUND);
+        }
+
+        let content = fs::read(&file_path).map_err(|e| {
+            error!("❌ Error reading file: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+        // While paused every viewer keeps getting the playlist as it was when the pause began,
+        // so players hold on the last frames and pick up the live edge again on resume
+        let paused = state.paused.load(std::sync::atomic::Ordering::SeqCst);
+        let content = if paused && path.ends_with(".m3u8") {
+            state
+                .paused_playlists
+                .lock()
+                .unwrap()
+                .entry(path.to_string())
+                .or_insert(content)
+                .clone()
+        } else {
+            content
+        };
+
+        let content = if path.ends_with(".m3u8") {
+            // Players that can't send headers need the code on every segment URI.
+            // Header-capable clients can ask for the untouched playlist with ?raw=1.
+            // Token viewers never see the code; their cookie authorizes the segments
+            let raw = !by_code || query.get("raw").map(|v| v == "1" || v == "true").unwrap_or(false);
+            let playlist = String::from_utf8_lossy(&content);
+            if raw {
+                playlist.into_owned().into_bytes()
+            } else {
+                rewrite_playlist_with_code(&playlist, &state.access_code).into_bytes()
+            }
+        } else {
+            content
+        };
+
+        let mut response_headers = axum::http::HeaderMap::new();
+        response_headers.insert(
+            axum::http::header::CONTENT_TYPE,
+            axum::http::HeaderValue::from_static(content_type),
+        );
+        if paused {
+            response_headers.insert("x-stream-paused", axum::http::HeaderValue::from_static("1"));
+        }
+
+        // Issue a session token on the first playlist fetch
+        if let Some(new_session) = new_session {
+            let cookie = format!(
+                "{}={}; Path=/; HttpOnly; SameSite=Lax",
+                VIEWER_SESSION_COOKIE,
+                new_session
+            );
+            if let Ok(value) = axum::http::HeaderValue::from_str(&cookie) {
+                response_headers.insert(axum::http::header::SET_COOKIE, value);
+            }
+        }
+
+        Ok((StatusCode::OK, response_headers, content).into_response())
+    }
+
+    // Handler for stream.m3u8 (no path param); in adaptive mode it's the master playlist
     async fn serve_stream_m3u8(
         State(state): State<Arc<HlsServerState>>,
         headers: axum::http::HeaderMap,
         query: axum::extract::Query<std::collections::HashMap<String, String>>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        // Validate access code
-        let provided_code = headers
-            .get("x-access-code")
-            .and_then(|h| h.to_str().ok())
-            .or_else(|| query.get("code").map(|s| s.as_str()));
-        
-        if let Some(code) = provided_code {
-            if code != state.access_code {
-                return Err(StatusCode::FORBIDDEN);
-            }
-        } else {
+    ) -> Result<Response, StatusCode> {
+        let playlist = if state.adaptive { HLS_MASTER_PLAYLIST } else { HLS_STREAM_PLAYLIST };
+        serve_hls_path(&state, playlist, &headers, &query).await
+    }
+
+    // Short looping GIF of the latest segments, for chat embeds and the viewer page
+    async fn serve_preview_gif(
+        State(state): State<Arc<HlsServerState>>,
+        headers: axum::http::HeaderMap,
+        query: axum::extract::Query<std::collections::HashMap<String, String>>,
+    ) -> Result<Response, StatusCode> {
+        if !state.preview_gif {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        if !is_allowed_client(&state, &headers) {
             return Err(StatusCode::FORBIDDEN);
         }
-        
-        // Track viewer
-        let client_ip = get_client_ip(&headers);
-        let user_agent = headers.get("user-agent")
-            .and_then(|h| h.to_str().ok());
-        track_viewer(&state, client_ip, user_agent);
-        
-        let file_path = state.public_dir.join("stream.m3u8");
-        if file_path.exists() {
-            let content = fs::read(&file_path).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            Ok((
-                StatusCode::OK,
-                [(axum::http::header::CONTENT_TYPE, "application/vnd.apple.mpegurl")],
-                content,
-            ))
-        } else {
-            Err(StatusCode::NOT_FOUND)
+        if !has_valid_code(&state, &headers, &query)
+            && !has_token_session(&state, get_viewer_session(&headers).as_deref())
+        {
+            error!("❌ Invalid or missing access code for: {}", PREVIEW_GIF_FILE_NAME);
+            return Err(StatusCode::FORBIDDEN);
         }
+
+        let gif_path = {
+            let _encoding = state.preview_lock.lock().await;
+            generate_preview_gif(&state.public_dir, &state.segment_prefix).await.map_err(|e| {
+                warn!("⚠️  Preview GIF not generated: {}", e);
+                StatusCode::SERVICE_UNAVAILABLE
+            })?
+        };
+        let content = fs::read(&gif_path).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let mut response_headers = axum::http::HeaderMap::new();
+        response_headers.insert(
+            axum::http::header::CONTENT_TYPE,
+            axum::http::HeaderValue::from_static("image/gif"),
+        );
+        Ok((StatusCode::OK, response_headers, content).into_response())
     }
-    
-    // Handler for segment files using a catch-all approach
+
+    // Catch-all for segments and any other playlists FFmpeg writes
     async fn serve_segment_catchall(
         uri: axum::http::Uri,
         State(state): State<Arc<HlsServerState>>,
         headers: axum::http::HeaderMap,
         query: axum::extract::Query<std::collections::HashMap<String, String>>,
-    ) -> Result<impl IntoResponse, StatusCode> {
+    ) -> Result<Response, StatusCode> {
         let path = uri.path().trim_start_matches('/');
-        eprintln!("📦 Request for: {}", path);
-        
-        // Only handle segment files
-        if !path.starts_with("segment_") || !path.ends_with(".ts") {
-            return Err(StatusCode::NOT_FOUND);
-        }
-        
-        // Validate access code
-        let provided_code = headers
-            .get("x-access-code")
-            .and_then(|h| h.to_str().ok())
-            .or_else(|| query.get("code").map(|s| s.as_str()));
-        
-        if let Some(code) = provided_code {
-            if code != state.access_code {
-                eprintln!("❌ Invalid access code for segment: {}", path);
-                return Err(StatusCode::FORBIDDEN);
-            }
-        } else {
-            eprintln!("❌ No access code provided for segment: {}", path);
-            return Err(StatusCode::FORBIDDEN);
-        }
-        
-        // Track viewer (update timestamp to keep them active)
-        let client_ip = get_client_ip(&headers);
-        let user_agent = headers.get("user-agent")
-            .and_then(|h| h.to_str().ok());
-        track_viewer(&state, client_ip, user_agent);
-        
-        let file_path = state.public_dir.join(path);
-        eprintln!("📁 Looking for file: {}", file_path.display());
-        
-        if file_path.exists() {
-            eprintln!("✅ Found segment file: {}", path);
-            let content = fs::read(&file_path).map_err(|e| {
-                eprintln!("❌ Error reading file: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
-            
-            Ok((
-                StatusCode::OK,
-                [(axum::http::header::CONTENT_TYPE, "video/mp2t")],
-                content,
-            ))
-        } else {
-            eprintln!("❌ Segment file not found: {}", path);
-            // List files in directory for debugging
-            if let Ok(entries) = fs::read_dir(&state.public_dir) {
-                eprintln!("📂 Files in public dir:");
-                for entry in entries.flatten() {
-                    if let Ok(name) = entry.file_name().into_string() {
-                        eprintln!("  - {}", name);
-                    }
-                }
-            }
-            Err(StatusCode::NOT_FOUND)
-        }
+        serve_hls_path(&state, path, &headers, &query).await
     }
     
     use axum::routing::any;
     
-    // Spawn cleanup task to remove stale viewers (older than 15 seconds)
-    // HLS clients typically request segments every 2 seconds, so 15 seconds is a safe timeout
+    // Spawn cleanup task to remove stale viewers
     let cleanup_state = state.clone();
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
+    let cleanup_task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(
+            cleanup_state.viewer_sweep_interval_secs,
+        ));
         loop {
             interval.tick().await;
             let mut viewers = cleanup_state.viewers.lock().unwrap();
             let now = SystemTime::now();
             let before_count = viewers.len();
-            let timeout_secs = 15; // Remove viewers inactive for 15 seconds
+            let timeout_secs = cleanup_state.viewer_timeout_secs;
             
-            viewers.retain(|ip, last_seen| {
-                if let Ok(duration) = now.duration_since(*last_seen) {
+            viewers.retain(|_, viewer| {
+                if let Ok(duration) = now.duration_since(viewer.last_seen) {
                     let is_active = duration.as_secs() < timeout_secs;
                     if !is_active {
-                        eprintln!("  🗑️  Removing inactive viewer: {} (last seen {}s ago)", ip, duration.as_secs());
+                        debug!("  🗑️  Removing inactive viewer: {} (last seen {}s ago)", viewer.ip, duration.as_secs());
                     }
                     is_active
                 } else {
-                    eprintln!("  🗑️  Removing viewer with invalid timestamp: {}", ip);
+                    debug!("  🗑️  Removing viewer with invalid timestamp: {}", viewer.ip);
                     false
                 }
             });
             let after_count = viewers.len();
             if before_count != after_count {
-                eprintln!("🧹 Cleaned up {} stale viewers. Active: {}", before_count - after_count, after_count);
+                info!("🧹 Cleaned up {} stale viewers. Active: {}", before_count - after_count, after_count);
+            }
+        }
+    });
+    
+    // Spawn poster task to refresh the preview frame periodically
+    let poster_dir = state.public_dir.clone();
+    let poster_prefix = state.segment_prefix.clone();
+    let poster_task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(POSTER_REFRESH_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = generate_poster_frame(&poster_dir, &poster_prefix).await {
+                warn!("⚠️  Poster frame not updated: {}", e);
             }
         }
     });
     
+    // Both tasks stop together with the server
+    let _background_tasks = AbortOnDrop(vec![cleanup_task, poster_task]);
+    
     let app = Router::new()
+        .route("/", get(serve_hls_viewer_page))
         .route("/api/info", get(hls_api_info))
         .route("/stream.m3u8", get(serve_stream_m3u8))
+        .route("/preview.gif", get(serve_preview_gif))
         .fallback(any(serve_segment_catchall))
         .layer(CorsLayer::permissive())
         .with_state(state.clone());
     
-    let addr = format!("127.0.0.1:{}", state.port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    eprintln!("✅ HLS server started on http://{}", addr);
-    eprintln!("   Access code: {}", state.access_code);
+    info!("✅ HLS server started on http://127.0.0.1:{}", state.port);
+    info!("   Access code: {}", state.access_code);
     
     axum::serve(listener, app).await?;
     Ok(())
 }
 
+// Set while start_hls_server_cmd is between its checks and storing the handle
+static HLS_STARTING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+// Clears HLS_STARTING on every return path, errors included
+struct HlsStartGuard;
+
+impl Drop for HlsStartGuard {
+    fn drop(&mut self) {
+        HLS_STARTING.store(false, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
 // Tauri command to start HLS server
 #[tauri::command]
 async fn start_hls_server_cmd(
     state: tauri::State<'_, Arc<Mutex<Option<HlsServerHandle>>>>,
     app_handle: tauri::AppHandle,
     device: Option<String>,
+    options: Option<HlsStartOptions>,
 ) -> Result<serde_json::Value, String> {
+    let options = options.unwrap_or_default();
+    let viewer_timeout_secs = options.viewer_timeout_secs.unwrap_or(DEFAULT_VIEWER_TIMEOUT_SECS);
+    // The sweep interval must be non-zero for tokio::time::interval
+    let viewer_sweep_interval_secs = options
+        .viewer_sweep_interval_secs
+        .unwrap_or(DEFAULT_VIEWER_SWEEP_INTERVAL_SECS)
+        .max(1);
+
+    // Only one start at a time, or two FFmpegs would fight over the public dir
+    if HLS_STARTING.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        return Err("HLS server is already starting".to_string());
+    }
+    let _starting = HlsStartGuard;
+
     // Check if server is already running
     {
-        let mut handle_opt = state.lock().unwrap();
+        let handle_opt = state.lock().unwrap();
         if handle_opt.is_some() {
             return Err("HLS server is already running".to_string());
         }
@@ -1746,58 +6904,156 @@ async fn start_hls_server_cmd(
         .map_err(|e| format!("Failed to get app data dir: {}", e))?;
     let public_dir = app_data_dir.join("hls_public");
     
-    // Generate access code
-    let access_code = generate_access_code();
+    // Use the supplied access code, otherwise generate one
+    let access_code = match options.access_code.as_deref() {
+        Some(code) => {
+            validate_access_code(code)?;
+            code.to_string()
+        }
+        None => generate_access_code(),
+    };
     let port = 3000u16;
+    let adaptive = options.adaptive.unwrap_or(false);
+    let segment_prefix = new_segment_prefix();
+    validate_extra_ffmpeg_args(&options.extra_ffmpeg_args)?;
+    let stream_title = options
+        .stream_title
+        .as_deref()
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .map(validate_stream_title)
+        .transpose()?;
+    if let Some(subdomain) = options.subdomain.as_deref() {
+        validate_subdomain(subdomain)?;
+    }
+    let allowed_cidrs = options
+        .allowed_cidrs
+        .iter()
+        .map(|cidr| parse_cidr(cidr))
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    // Claim the port before spawning anything, so a conflict can't leave FFmpeg capturing
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("Port {} is unavailable: {}", port, e))?;
     
     let hls_state = Arc::new(HlsServerState {
         access_code: access_code.clone(),
         port,
         public_dir: public_dir.clone(),
         viewers: Arc::new(Mutex::new(std::collections::HashMap::new())),
+        viewer_timeout_secs,
+        viewer_sweep_interval_secs,
+        adaptive,
+        preview_gif: options.preview_gif.unwrap_or(false),
+        preview_lock: Arc::new(tokio::sync::Mutex::new(())),
+        segment_prefix: segment_prefix.clone(),
+        verbose_logging: options.verbose_hls_logging.unwrap_or(false),
+        stream_title: stream_title.clone(),
+        allowed_cidrs,
+        viewer_tokens: Arc::new(Mutex::new(std::collections::HashSet::new())),
+        token_sessions: Arc::new(Mutex::new(std::collections::HashSet::new())),
+        ended: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        paused: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        paused_playlists: Arc::new(Mutex::new(std::collections::HashMap::new())),
     });
     
     // Start FFmpeg with device selection
+    let device = match device {
+        Some(device) => {
+            validate_device_spec(&device)?;
+            Some(device)
+        }
+        None => default_capture_device().await?,
+    };
+    let encoder = resolve_video_encoder(options.encoder.as_deref()).await?;
+    info!("🎞️  Using video encoder: {}", encoder);
     let device_str = device.as_deref();
-    let mut ffmpeg_handle = start_ffmpeg(&public_dir, device_str)
-        .await
-        .map_err(|e| format!("Failed to start FFmpeg: {}", e))?;
+    let window = match options.window_id {
+        Some(window_id) => Some(find_capturable_window(window_id).await?),
+        None => None,
+    };
+    if let Some(window) = window.as_ref() {
+        info!("🪟 Capturing window {} ({} - {})", window.id, window.owner, window.title);
+    }
+    let mut ffmpeg_handle = start_ffmpeg(
+        &public_dir,
+        device_str,
+        &encoder,
+        window.as_ref(),
+        &options,
+        &segment_prefix,
+    )
+    .await
+    .map_err(|e| format!("Failed to start FFmpeg: {}", e))?;
     
     // Get FFmpeg PID (id() returns Option<u32> on all platforms)
     let ffmpeg_pid = ffmpeg_handle.id();
     
     if let Some(pid) = ffmpeg_pid {
-        eprintln!("📹 FFmpeg started with PID: {}", pid);
+        info!("📹 FFmpeg started with PID: {}", pid);
     } else {
-        eprintln!("📹 FFmpeg started (PID not available)");
+        info!("📹 FFmpeg started (PID not available)");
+    }
+    
+    // Drain stderr for the lifetime of the process; an unread pipe would eventually block FFmpeg
+    let ffmpeg_log = Arc::new(Mutex::new(std::collections::VecDeque::new()));
+    let actual_resolution = Arc::new(Mutex::new(None));
+    if let Some(stderr) = ffmpeg_handle.stderr.take() {
+        spawn_ffmpeg_log_reader(stderr, ffmpeg_log.clone(), actual_resolution.clone());
+    }
+    
+    // Fail fast on a bad device or missing permission instead of serving 404s forever
+    let playlist = public_dir.join(if adaptive { HLS_MASTER_PLAYLIST } else { HLS_STREAM_PLAYLIST });
+    if let Err(e) = wait_for_ffmpeg_startup(&mut ffmpeg_handle, &playlist, &ffmpeg_log).await {
+        let _ = kill_process_forcefully(&mut ffmpeg_handle, ffmpeg_pid).await;
+        return Err(e);
     }
     
     // Start HTTP server
     let server_state = hls_state.clone();
     let server_handle = tokio::spawn(async move {
-        start_hls_server(server_state).await
+        start_hls_server(server_state, listener).await
     });
     
     // Start localtunnel
-    let (tunnel_handle, tunnel_url, tunnel_domain, tunnel_pid) = match start_localtunnel(port).await {
-        Ok((mut handle, url, domain)) => {
+    let (tunnel_handle, tunnel_url, tunnel_domain, tunnel_pid) = match start_localtunnel(port, options.subdomain.as_deref()).await {
+        Ok((handle, url, domain)) => {
             // Get tunnel PID (id() returns Option<u32> on all platforms)
             let pid = handle.id();
             
             if let Some(p) = pid {
-                eprintln!("🌐 Tunnel started with PID: {}", p);
+                info!("🌐 Tunnel started with PID: {}", p);
             } else {
-                eprintln!("🌐 Tunnel started (PID not available)");
+                info!("🌐 Tunnel started (PID not available)");
+            }
+            if let Some(subdomain) = options.subdomain.as_deref() {
+                if !domain.starts_with(&format!("{}.", subdomain)) {
+                    warn!("⚠️  Subdomain '{}' was not available, localtunnel assigned {}", subdomain, domain);
+                }
             }
             (Some(handle), Some(url), Some(domain), pid)
         }
         Err(e) => {
-            eprintln!("⚠️  Failed to create tunnel: {}", e);
-            eprintln!("   Server still running on localhost - tunnel creation failed");
+            warn!("⚠️  Failed to create tunnel: {}", e);
+            info!("   Server still running on localhost - tunnel creation failed");
             (None, None, None, None)
         }
     };
     
+    // Viewers have to enter this on localtunnel's reminder page before the stream loads
+    let tunnel_password = if tunnel_url.is_some() {
+        match fetch_tunnel_password().await {
+            Ok(password) => Some(password),
+            Err(e) => {
+                warn!("⚠️  Could not fetch tunnel password: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    
     // Store handle
     {
         let mut handle_opt = state.lock().unwrap();
@@ -1811,8 +7067,16 @@ async fn start_hls_server_cmd(
             port,
             tunnel_url: tunnel_url.clone(),
             tunnel_domain: tunnel_domain.clone(),
+            tunnel_password: tunnel_password.clone(),
             public_dir: public_dir.clone(),
             viewers: hls_state.viewers.clone(),
+            viewer_tokens: hls_state.viewer_tokens.clone(),
+            ended: hls_state.ended.clone(),
+            paused: hls_state.paused.clone(),
+            paused_playlists: hls_state.paused_playlists.clone(),
+            stream_title: hls_state.stream_title.clone(),
+            ffmpeg_log,
+            actual_resolution,
         });
     }
     
@@ -1820,11 +7084,26 @@ async fn start_hls_server_cmd(
         "code": access_code,
         "port": port,
         "url": format!("http://localhost:{}", port),
+        "encoder": encoder,
+        "systemAudio": options.include_system_audio.unwrap_or(false),
+        "title": stream_title,
     });
+    if adaptive {
+        let renditions: Vec<&str> = HLS_RENDITIONS.iter().map(|(name, _, _)| *name).collect();
+        response["renditions"] = serde_json::json!(renditions);
+    }
     
     if let (Some(ref url), Some(ref domain)) = (tunnel_url, tunnel_domain) {
         response["tunnelUrl"] = serde_json::Value::String(url.clone());
         response["tunnelDomain"] = serde_json::Value::String(domain.clone());
+        if let Some(password) = tunnel_password {
+            response["tunnelPassword"] = serde_json::Value::String(password);
+        }
+        // Tell the caller whether the requested subdomain was actually granted
+        if let Some(subdomain) = options.subdomain.as_deref() {
+            response["subdomainGranted"] =
+                serde_json::Value::Bool(domain.starts_with(&format!("{}.", subdomain)));
+        }
     }
     
     Ok(response)
@@ -1843,22 +7122,22 @@ async fn kill_process_tree_windows(pid: u32) -> Result<(), String> {
     match output {
         Ok(output) => {
             if output.status.success() {
-                eprintln!("✅ Killed process tree for PID {}", pid);
+                info!("✅ Killed process tree for PID {}", pid);
                 Ok(())
             } else {
                 let stderr = String::from_utf8_lossy(&output.stderr);
                 // "The process not found" is okay - it might already be dead
                 if stderr.contains("not found") {
-                    eprintln!("ℹ️  Process {} already terminated", pid);
+                    info!("ℹ️  Process {} already terminated", pid);
                     Ok(())
                 } else {
-                    eprintln!("⚠️  taskkill warning for PID {}: {}", pid, stderr);
+                    warn!("⚠️  taskkill warning for PID {}: {}", pid, stderr);
                     Ok(()) // Still return Ok, process might be dead
                 }
             }
         }
         Err(e) => {
-            eprintln!("⚠️  Failed to run taskkill for PID {}: {}", pid, e);
+            warn!("⚠️  Failed to run taskkill for PID {}: {}", pid, e);
             Err(format!("Failed to kill process: {}", e))
         }
     }
@@ -1873,17 +7152,17 @@ async fn kill_process_forcefully(child: &mut tokio::process::Child, pid: Option<
             // Try to get PID from child if not provided
             let actual_pid = pid;
             if let Err(e) = kill_process_tree_windows(actual_pid).await {
-                eprintln!("⚠️  Failed to kill process tree, trying direct kill: {}", e);
+                warn!("⚠️  Failed to kill process tree, trying direct kill: {}", e);
                 // Fallback to direct kill
                 if let Err(e) = child.kill().await {
-                    eprintln!("⚠️  Direct kill also failed: {}", e);
+                    warn!("⚠️  Direct kill also failed: {}", e);
                     return Err(format!("Failed to kill process: {}", e));
                 }
             }
         } else {
             // No PID, try direct kill
             if let Err(e) = child.kill().await {
-                eprintln!("⚠️  Direct kill failed: {}", e);
+                warn!("⚠️  Direct kill failed: {}", e);
                 return Err(format!("Failed to kill process: {}", e));
             }
         }
@@ -1898,8 +7177,9 @@ async fn kill_process_forcefully(child: &mut tokio::process::Child, pid: Option<
     #[cfg(not(target_os = "windows"))]
     {
         // On macOS/Linux, direct kill should work
+        let _ = pid;
         if let Err(e) = child.kill().await {
-            eprintln!("⚠️  Failed to kill process: {}", e);
+            warn!("⚠️  Failed to kill process: {}", e);
             return Err(format!("Failed to kill process: {}", e));
         }
         
@@ -1910,6 +7190,38 @@ async fn kill_process_forcefully(child: &mut tokio::process::Child, pid: Option<
     Ok(())
 }
 
+// How long the server keeps answering after the stream is marked ended, so players polling
+// the playlist see the end marker instead of a 404
+const STREAM_END_GRACE_MS: u64 = 1000;
+
+// FFmpeg is killed rather than asked to finish, so it never writes the end tag itself.
+// Appending it to every media playlist tells players the stream is over
+fn mark_hls_playlists_ended(public_dir: &Path) {
+    let Ok(entries) = fs::read_dir(public_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_media_playlist = path.extension().is_some_and(|ext| ext == "m3u8")
+            && path.file_name().is_some_and(|name| name != HLS_MASTER_PLAYLIST);
+        if !is_media_playlist {
+            continue;
+        }
+        if let Ok(mut playlist) = fs::read_to_string(&path) {
+            if playlist.contains("#EXT-X-ENDLIST") {
+                continue;
+            }
+            if !playlist.ends_with('\n') {
+                playlist.push('\n');
+            }
+            playlist.push_str("#EXT-X-ENDLIST\n");
+            if let Err(e) = fs::write(&path, playlist) {
+                warn!("⚠️  Failed to mark {} as ended: {}", path.display(), e);
+            }
+        }
+    }
+}
+
 // Tauri command to stop HLS server
 #[tauri::command]
 async fn stop_hls_server_cmd(
@@ -1921,48 +7233,97 @@ async fn stop_hls_server_cmd(
     };
     
     if let Some(mut handle) = handle_opt {
-        eprintln!("🛑 Stopping HLS server...");
+        info!("🛑 Stopping HLS server...");
         
         // Kill FFmpeg
         if let Some(mut ffmpeg) = handle.ffmpeg_handle.take() {
-            eprintln!("  Killing FFmpeg process...");
+            info!("  Killing FFmpeg process...");
             let ffmpeg_pid = handle.ffmpeg_pid;
             if let Err(e) = kill_process_forcefully(&mut ffmpeg, ffmpeg_pid).await {
-                eprintln!("⚠️  Warning: Failed to kill FFmpeg: {}", e);
+                warn!("⚠️  Warning: Failed to kill FFmpeg: {}", e);
             } else {
-                eprintln!("  ✅ FFmpeg stopped");
+                info!("  ✅ FFmpeg stopped");
             }
         }
         
+        // Let viewers see the end of the stream before the server and tunnel go away
+        handle.ended.store(true, std::sync::atomic::Ordering::SeqCst);
+        mark_hls_playlists_ended(&handle.public_dir);
+        tokio::time::sleep(tokio::time::Duration::from_millis(STREAM_END_GRACE_MS)).await;
+        
         // Kill tunnel
         if let Some(mut tunnel) = handle.tunnel_handle.take() {
-            eprintln!("  Killing tunnel process...");
+            info!("  Killing tunnel process...");
             let tunnel_pid = handle.tunnel_pid;
             if let Err(e) = kill_process_forcefully(&mut tunnel, tunnel_pid).await {
-                eprintln!("⚠️  Warning: Failed to kill tunnel: {}", e);
+                warn!("⚠️  Warning: Failed to kill tunnel: {}", e);
             } else {
-                eprintln!("  ✅ Tunnel stopped");
+                info!("  ✅ Tunnel stopped");
             }
         }
         
         // Abort server task
-        eprintln!("  Stopping HTTP server...");
+        info!("  Stopping HTTP server...");
         handle.server_handle.abort();
-        eprintln!("  ✅ HTTP server stopped");
+        info!("  ✅ HTTP server stopped");
         
         // Clean up HLS directory
-        eprintln!("🧹 Cleaning up HLS directory on server stop...");
+        info!("🧹 Cleaning up HLS directory on server stop...");
         if let Err(e) = cleanup_hls_directory(&handle.public_dir) {
-            eprintln!("⚠️  Warning: Failed to cleanup HLS directory: {}", e);
+            warn!("⚠️  Warning: Failed to cleanup HLS directory: {}", e);
         }
         
-        eprintln!("✅ HLS server fully stopped");
+        info!("✅ HLS server fully stopped");
         Ok(())
     } else {
         Err("HLS server is not running".to_string())
     }
 }
 
+// Panic button: pause clipboard capture, cancel index jobs and shut down every server,
+// reporting what was stopped. The auto reindexer is a setting and keeps its schedule
+#[tauri::command]
+async fn stop_all_activity(
+    app_handle: tauri::AppHandle,
+    db: tauri::State<'_, Arc<Mutex<ClipboardDatabase>>>,
+    monitor: tauri::State<'_, Arc<Mutex<ClipboardMonitorState>>>,
+    jobs: tauri::State<'_, Arc<Mutex<JobRegistry>>>,
+    share_state: tauri::State<'_, Arc<Mutex<Option<ClipboardShareHandle>>>>,
+    hls_state: tauri::State<'_, Arc<Mutex<Option<HlsServerHandle>>>>,
+) -> Result<serde_json::Value, String> {
+    let clipboard_paused = {
+        let db = db.lock().map_err(|e| e.to_string())?;
+        let mut monitor = monitor.lock().map_err(|e| e.to_string())?;
+        apply_clipboard_paused(&app_handle, &db, &mut monitor, true)
+    };
+    // Same as cancel_job for each; they finish with a cancelled job-done event
+    let index_jobs_cancelled = {
+        let registry = jobs.lock().map_err(|e| e.to_string())?;
+        registry
+            .jobs
+            .values()
+            .filter(|entry| !entry.cancel.swap(true, std::sync::atomic::Ordering::SeqCst))
+            .count()
+    };
+    let clipboard_share = stop_clipboard_share(share_state).await.is_ok();
+    let hls_server = stop_hls_server_cmd(hls_state).await.is_ok();
+    info!("🛑 Stopped all activity");
+
+    Ok(serde_json::json!({
+        "clipboardMonitor": clipboard_paused,
+        "indexJobsCancelled": index_jobs_cancelled,
+        "autoReindexStopped": false,
+        "clipboardShare": clipboard_share,
+        "hlsServer": hls_server,
+    }))
+}
+
+// Count active viewers as (sessions, distinct IPs)
+fn count_viewers(viewers: &std::collections::HashMap<String, ViewerInfo>) -> (usize, usize) {
+    let ips: std::collections::HashSet<&str> = viewers.values().map(|v| v.ip.as_str()).collect();
+    (viewers.len(), ips.len())
+}
+
 // Tauri command to get HLS server info
 #[tauri::command]
 async fn get_hls_server_info(
@@ -1971,25 +7332,42 @@ async fn get_hls_server_info(
     let handle_opt = state.lock().unwrap();
     if let Some(handle) = handle_opt.as_ref() {
         // Get viewer count
-        let viewer_count = {
+        let (viewer_count, unique_ips) = {
             let viewers = handle.viewers.lock().unwrap();
-            viewers.len()
+            count_viewers(&viewers)
         };
         
         let mut info = serde_json::json!({
             "running": true,
+            "paused": handle.paused.load(std::sync::atomic::Ordering::SeqCst),
+            "title": handle.stream_title,
             "code": handle.access_code,
             "port": handle.port,
             "url": format!("http://localhost:{}", handle.port),
             "viewers": viewer_count,
+            "uniqueSessions": viewer_count,
+            "uniqueIps": unique_ips,
         });
         
+        if let Some(resolution) = handle.actual_resolution.lock().ok().and_then(|r| r.clone()) {
+            info["actualResolution"] = serde_json::Value::String(resolution);
+        }
+        
+        // Tail of FFmpeg's stderr so capture problems can be diagnosed from the UI
+        if let Ok(log) = handle.ffmpeg_log.lock() {
+            let tail: Vec<&String> = log.iter().skip(log.len().saturating_sub(20)).collect();
+            info["ffmpegLog"] = serde_json::json!(tail);
+        }
+        
         if let Some(ref tunnel_url) = handle.tunnel_url {
             info["tunnelUrl"] = serde_json::Value::String(tunnel_url.clone());
         }
         if let Some(ref tunnel_domain) = handle.tunnel_domain {
             info["tunnelDomain"] = serde_json::Value::String(tunnel_domain.clone());
         }
+        if let Some(ref tunnel_password) = handle.tunnel_password {
+            info["tunnelPassword"] = serde_json::Value::String(tunnel_password.clone());
+        }
         
         Ok(Some(info))
     } else {
@@ -1997,6 +7375,60 @@ async fn get_hls_server_info(
     }
 }
 
+// Tauri command to hold the stream without stopping FFmpeg or the tunnel
+#[tauri::command]
+async fn pause_hls_stream(
+    state: tauri::State<'_, Arc<Mutex<Option<HlsServerHandle>>>>,
+) -> Result<(), String> {
+    let handle_opt = state.lock().unwrap();
+    let handle = handle_opt.as_ref().ok_or_else(|| "HLS server is not running".to_string())?;
+    if !handle.paused.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        // A request racing the last resume may have left a stale snapshot behind
+        handle.paused_playlists.lock().unwrap().clear();
+        info!("⏸️  HLS stream paused");
+    }
+    Ok(())
+}
+
+// Tauri command to go live again after pause_hls_stream
+#[tauri::command]
+async fn resume_hls_stream(
+    state: tauri::State<'_, Arc<Mutex<Option<HlsServerHandle>>>>,
+) -> Result<(), String> {
+    let handle_opt = state.lock().unwrap();
+    let handle = handle_opt.as_ref().ok_or_else(|| "HLS server is not running".to_string())?;
+    if handle.paused.swap(false, std::sync::atomic::Ordering::SeqCst) {
+        handle.paused_playlists.lock().unwrap().clear();
+        info!("▶️  HLS stream resumed");
+    }
+    Ok(())
+}
+
+// Tauri command to issue a viewer link that works once, in place of sharing the access code
+#[tauri::command]
+async fn create_hls_viewer_token(
+    state: tauri::State<'_, Arc<Mutex<Option<HlsServerHandle>>>>,
+) -> Result<serde_json::Value, String> {
+    let handle_opt = state.lock().unwrap();
+    let handle = handle_opt.as_ref().ok_or_else(|| "HLS server is not running".to_string())?;
+
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    handle.viewer_tokens.lock().unwrap().insert(token.clone());
+
+    let mut response = serde_json::json!({
+        "token": token,
+        "url": format!("http://localhost:{}/stream.m3u8?token={}", handle.port, token),
+    });
+    if let Some(ref tunnel_url) = handle.tunnel_url {
+        response["tunnelUrl"] = serde_json::Value::String(format!(
+            "{}/stream.m3u8?token={}",
+            tunnel_url.trim_end_matches('/'),
+            token
+        ));
+    }
+    Ok(response)
+}
+
 // Tauri command to get viewer count
 #[tauri::command]
 async fn get_hls_viewer_count(
@@ -2011,7 +7443,185 @@ async fn get_hls_viewer_count(
     }
 }
 
+// Tauri command to get viewer stats (sessions vs. distinct IPs)
+#[tauri::command]
+async fn get_hls_viewer_stats(
+    state: tauri::State<'_, Arc<Mutex<Option<HlsServerHandle>>>>,
+) -> Result<serde_json::Value, String> {
+    let handle_opt = state.lock().unwrap();
+    let (sessions, ips) = match handle_opt.as_ref() {
+        Some(handle) => count_viewers(&handle.viewers.lock().unwrap()),
+        None => (0, 0),
+    };
+
+    Ok(serde_json::json!({
+        "sessions": sessions,
+        "ips": ips,
+    }))
+}
+
+// Total size of a file, or of everything under a directory; missing paths count as 0
+fn path_size(path: &Path) -> u64 {
+    if path.is_file() {
+        return path.metadata().map(|m| m.len()).unwrap_or(0);
+    }
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+// Disk usage of PathFinder's own data, broken down so the UI can pair it with the clear_* commands
+#[tauri::command]
+fn get_storage_usage(app_handle: tauri::AppHandle) -> Result<serde_json::Value, String> {
+    let app_data_dir = app_handle.path().app_data_dir().map_err(|e| e.to_string())?;
+
+    // Every profile's history and archive count toward clipboard usage
+    let clipboard_files_size = |prefix: &str, extension: &str| -> u64 {
+        fs::read_dir(&app_data_dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter(|e| {
+                        let name = e.file_name().to_string_lossy().to_string();
+                        name.starts_with(prefix) && name.ends_with(extension)
+                    })
+                    .map(|e| path_size(&e.path()))
+                    .sum()
+            })
+            .unwrap_or(0)
+    };
+    let clipboard_archive = clipboard_files_size("clipboard_archive", ".ndjson");
+    let clipboard = clipboard_files_size("clipboard_history", ".json") + clipboard_archive;
+    let file_index = path_size(&get_file_search_db_path(&app_handle));
+    let hls = path_size(&app_data_dir.join("hls_public"));
+    let logs = app_handle
+        .path()
+        .app_log_dir()
+        .map(|dir| path_size(&dir))
+        .unwrap_or(0);
+    let temp = path_size(&get_editor_temp_dir());
+
+    Ok(serde_json::json!({
+        "clipboard": clipboard,
+        "clipboardArchive": clipboard_archive, // Already included in clipboard
+        "fileIndex": file_index,
+        "hls": hls,
+        "logs": logs,
+        "temp": temp,
+        "total": clipboard + file_index + hls + logs + temp,
+    }))
+}
+
+// Tauri command aggregating the state of every subsystem for a diagnostics panel
+#[tauri::command]
+async fn get_system_status(
+    clipboard_db: tauri::State<'_, Arc<Mutex<ClipboardDatabase>>>,
+    monitor: tauri::State<'_, Arc<Mutex<ClipboardMonitorState>>>,
+    file_db: tauri::State<'_, Arc<Mutex<FileSearchDatabase>>>,
+    settings: tauri::State<'_, Arc<Mutex<AppSettings>>>,
+    jobs: tauri::State<'_, Arc<Mutex<JobRegistry>>>,
+    hls_state: tauri::State<'_, Arc<Mutex<Option<HlsServerHandle>>>>,
+) -> Result<serde_json::Value, String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let clipboard = {
+        let db = clipboard_db.lock().map_err(|e| e.to_string())?;
+        let monitor = monitor.lock().map_err(|e| e.to_string())?;
+        serde_json::json!({
+            "running": CLIPBOARD_MONITOR_RUNNING.load(std::sync::atomic::Ordering::SeqCst),
+            "paused": monitor.paused,
+            "incognito": monitor.incognito,
+            "items": db.items.len(),
+        })
+    };
+
+    let auto_reindex_interval_secs = settings.lock().map_err(|e| e.to_string())?.auto_reindex_interval_secs;
+    let active_jobs = jobs.lock().map_err(|e| e.to_string())?.jobs.len();
+    let file_index = {
+        let db = file_db.lock().map_err(|e| e.to_string())?;
+        serde_json::json!({
+            "reindexing": REINDEXING.load(std::sync::atomic::Ordering::SeqCst),
+            "activeJobs": active_jobs,
+            // 0 means the auto reindexer is off
+            "autoReindexIntervalSecs": auto_reindex_interval_secs,
+            "files": db.files.len(),
+            "apps": db.apps.len(),
+            "lastIndexed": db.last_indexed,
+            "appsIndexed": db.apps_indexed,
+            "filesIndexed": db.files_indexed,
+            "ageSecs": if db.last_indexed > 0 { Some(now.saturating_sub(db.last_indexed)) } else { None },
+        })
+    };
+
+    let hls = {
+        let handle_opt = hls_state.lock().map_err(|e| e.to_string())?;
+        match handle_opt.as_ref() {
+            Some(handle) => {
+                let (viewers, _) = count_viewers(&handle.viewers.lock().unwrap());
+                serde_json::json!({
+                    "running": true,
+                    "viewers": viewers,
+                    "tunnelUrl": handle.tunnel_url,
+                })
+            }
+            None => serde_json::json!({ "running": false, "viewers": 0 }),
+        }
+    };
+
+    Ok(serde_json::json!({
+        "clipboard": clipboard,
+        "fileIndex": file_index,
+        "hls": hls,
+    }))
+}
+
+// Reload handle for the global log filter, so verbosity can change without a restart
+pub struct LogLevelHandle(reload::Handle<LevelFilter, Registry>);
+
+const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::INFO;
+
+// Install the stderr subscriber; the returned handle adjusts its level at runtime
+fn init_logging() -> LogLevelHandle {
+    let (filter, handle) = reload::Layer::new(DEFAULT_LOG_LEVEL);
+    let _ = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(std::io::stderr).with_target(false))
+        .try_init();
+    LogLevelHandle(handle)
+}
+
+// Tauri command to change log verbosity (off, error, warn, info, debug, trace)
+#[tauri::command]
+fn set_log_level(level: String, log_level: tauri::State<LogLevelHandle>) -> Result<String, String> {
+    let filter: LevelFilter = level
+        .trim()
+        .parse()
+        .map_err(|_| format!("Unknown log level '{}'", level))?;
+    log_level.0.reload(filter).map_err(|e| e.to_string())?;
+    info!("🔧 Log level set to {}", filter);
+    Ok(filter.to_string().to_lowercase())
+}
+
+// Tauri command to read the current log level
+#[tauri::command]
+fn get_log_level(log_level: tauri::State<LogLevelHandle>) -> Result<String, String> {
+    log_level
+        .0
+        .clone_current()
+        .map(|filter| filter.to_string().to_lowercase())
+        .ok_or_else(|| "Logging is not initialized".to_string())
+}
+
 pub fn run() {
+    let log_level_handle = init_logging();
+
     // --- FIX 1: Define the handler logic ---
     // This handler will be attached to the main builder.
     // It must be able to check *which* shortcut was pressed.
@@ -2023,43 +7633,71 @@ pub fn run() {
             if scut.id() == shortcut.id() && event.state() == ShortcutState::Pressed {
                 let win = app.get_webview_window("main").expect("window not found");
                 if win.is_visible().unwrap_or(false) {
-                    let _ = win.hide();
+                    hide_main_window(app);
                 } else {
-                    let _ = win.show();
-                    let _ = win.set_focus();
+                    show_main_window(app);
                 }
+            } else if event.state() == ShortcutState::Released
+                && configured_quick_paste_shortcut(app).map(|s| s.id()) == Some(scut.id())
+            {
+                quick_paste(app);
             }
         })
         .build();
 
     tauri::Builder::default()
+        .manage(log_level_handle)
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_clipboard_manager::init())
         // --- Add the handler plugin ---
         .plugin(shortcut_handler)
         .setup(|app| {
-            // Initialize clipboard database
-            let db_path = get_db_path(&app.handle());
+            // Load settings first, other subsystems read from them
+            let settings_path = get_settings_path(app.handle());
             
             // Create app data directory if it doesn't exist
-            if let Some(parent) = db_path.parent() {
+            if let Some(parent) = settings_path.parent() {
                 fs::create_dir_all(parent).expect("Failed to create app data directory");
             }
             
+            let settings = Arc::new(Mutex::new(
+                load_settings(&settings_path).unwrap_or_default()
+            ));
+            app.manage(settings.clone());
+
+            // Snippets opened in the editor last session are no longer needed
+            let _ = fs::remove_dir_all(get_editor_temp_dir());
+
+            // Initialize clipboard database for the active profile
+            let db_path = get_db_path(app.handle());
+
             let db = Arc::new(Mutex::new(
                 load_db(&db_path).unwrap_or_else(|_| ClipboardDatabase::new(100))
             ));
             app.manage(db.clone());
 
             // Initialize file search database
-            let file_db_path = get_file_search_db_path(&app.handle());
+            let file_db_path = get_file_search_db_path(app.handle());
             let file_db = Arc::new(Mutex::new(
                 load_file_db(&file_db_path).unwrap_or_else(|_| FileSearchDatabase::new())
             ));
+            let apps_cache = AppsCache {
+                apps: file_db.lock().map(|db| db.apps.clone()).unwrap_or_default(),
+            };
+            app.manage(Arc::new(Mutex::new(apps_cache)));
             app.manage(file_db.clone());
+            app.manage(Arc::new(Mutex::new(PerfMetrics::default())));
+            app.manage(Arc::new(Mutex::new(JobRegistry::default())));
+            start_auto_reindexer(app.handle().clone());
 
             // Start clipboard monitor
-            start_clipboard_monitor(app.handle().clone(), db.clone());
+            let monitor_state = Arc::new(Mutex::new(ClipboardMonitorState::default()));
+            app.manage(monitor_state.clone());
+            start_clipboard_monitor(app.handle().clone(), db.clone(), monitor_state);
+            start_clipboard_saver(app.handle().clone());
+
+            // Clipboard share is off until explicitly started
+            app.manage(Arc::new(Mutex::new(None::<ClipboardShareHandle>)));
 
             // Initialize HLS server state
             let hls_server_state = Arc::new(Mutex::new(None::<HlsServerHandle>));
@@ -2074,28 +7712,295 @@ pub fn run() {
                     Shortcut::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::Space);
                 
                 app.global_shortcut().register(shortcut)?;
+                
+                if let Some(quick_paste_shortcut) = configured_quick_paste_shortcut(app.handle()) {
+                    if let Err(e) = app.global_shortcut().register(quick_paste_shortcut) {
+                        warn!("⚠️  Failed to register quick paste shortcut: {}", e);
+                    }
+                }
             }
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_clipboard_history,
+            get_current_clipboard_item,
+            search_clipboard,
+            search_archive,
             update_clipboard_access,
             delete_clipboard_item,
             clear_clipboard_history,
+            export_clipboard_ndjson,
+            export_clipboard_items,
+            import_clipboard_ndjson,
+            set_clipboard_incognito,
+            clipboard_monitor_status,
+            set_clipboard_paused,
+            set_event_filter,
             paste_clipboard_item,
+            paste_transformed,
+            test_paste,
+            check_accessibility_permission,
             search_files,
+            search_in_folder,
+            benchmark_search,
             get_applications,
+            search_apps,
             get_recent_files,
             open_file,
+            run_quick_action,
+            get_file_preview,
+            copy_file_contents,
             refresh_file_index,
+            refresh_apps_only,
+            refresh_files_only,
+            get_index_checkpoint,
+            start_job,
+            cancel_job,
+            list_jobs,
+            compact_file_index,
+            verify_file_index,
+            save_session_state,
+            load_session_state,
+            get_settings,
+            update_settings,
             hide_window,
+            copy_and_hide,
+            validate_shortcut,
             check_ffmpeg,
+            get_ffmpeg_info,
             list_ffmpeg_devices,
             start_hls_server_cmd,
             stop_hls_server_cmd,
             get_hls_server_info,
             get_hls_viewer_count,
+            create_hls_viewer_token,
+            pause_hls_stream,
+            resume_hls_stream,
+            get_hls_viewer_stats,
+            start_clipboard_share,
+            check_localtunnel,
+            copy_as_markdown_link,
+            list_profiles,
+            create_profile,
+            switch_profile,
+            get_perf_metrics,
+            open_clipboard_item_in_editor,
+            hide_app,
+            unhide_app,
+            get_storage_usage,
+            flush_clipboard_db,
+            pin_clipboard_item,
+            rate_clipboard_item,
+            dedup_clipboard_history,
+            reorder_pinned_items,
+            open_latest_image,
+            open_clipboard_item_as_path,
+            resync_clipboard,
+            list_capturable_windows,
+            list_capture_displays,
+            stop_clipboard_share,
+            get_system_status,
+            stop_all_activity,
+            set_log_level,
+            get_log_level,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri");
-}
\ No newline at end of file
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            // Don't lose access stats or captures still waiting for the saver
+            if let tauri::RunEvent::Exit = event {
+                if let Err(e) = flush_pending_clipboard(app_handle) {
+                    warn!("⚠️  Failed to save clipboard history on exit: {}", e);
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clipboard_item(id: &str, content: &str, last_accessed: u64) -> ClipboardItem {
+        ClipboardItem {
+            id: id.to_string(),
+            content: content.to_string(),
+            content_type: classify_content(content).to_string(),
+            created_at: last_accessed,
+            last_accessed,
+            access_count: 0,
+            source: CLIPBOARD_SOURCE_SYSTEM.to_string(),
+            size: content.len(),
+            pinned: false,
+            rating: 0,
+            content_hash: content_hash(content),
+            pin_order: None,
+            age_secs: None,
+        }
+    }
+
+    #[test]
+    fn incognito_add_item_keeps_persisted_history() {
+        let settings = AppSettings::default();
+        let mut db = ClipboardDatabase::new(2);
+        db.add_item(clipboard_item("a", "alpha", 1), &settings, None);
+        db.add_item(clipboard_item("b", "beta", 2), &settings, None);
+
+        let mut incognito_ids = Vec::new();
+        for (id, content) in [("x", "secret one"), ("y", "secret two")] {
+            let evicted = db.add_item(clipboard_item(id, content, 3), &settings, Some(&incognito_ids));
+            assert!(evicted.iter().all(|item| item.id == "x"));
+            incognito_ids.push(id.to_string());
+        }
+        // Over the limit, but only the in-memory capture was evicted
+        let ids: Vec<&str> = db.items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["y", "b", "a"]);
+
+        // Re-copying a persisted item doesn't touch its stats
+        db.add_item(clipboard_item("z", "alpha", 9), &settings, Some(&incognito_ids));
+        let alpha = db.items.iter().find(|i| i.id == "a").unwrap();
+        assert_eq!((alpha.access_count, alpha.last_accessed), (0, 1));
+    }
+
+    #[test]
+    fn editor_extension_follows_content() {
+        assert_eq!(editor_extension_for("{\"name\": \"pathfinder\", \"tags\": [1, 2]}"), "json");
+        assert_eq!(editor_extension_for("  [1, 2, 3]\n"), "json");
+        assert_eq!(editor_extension_for("<div><p>hi</p></div>"), "html");
+        assert_eq!(editor_extension_for("# Notes\n\n- one\n- two"), "md");
+        assert_eq!(editor_extension_for("{not json"), "txt");
+        assert_eq!(editor_extension_for("#hashtag and 42"), "txt");
+    }
+
+    #[test]
+    fn percent_decode_requires_two_hex_digits() {
+        assert_eq!(percent_decode("a%20b%2Fc").unwrap(), "a b/c");
+        assert!(percent_decode("%+1").is_err());
+        assert!(percent_decode("%-1").is_err());
+        assert!(percent_decode("%zz").is_err());
+        assert!(percent_decode("%4").is_err());
+    }
+
+    #[test]
+    fn coalescing_only_compares_the_previous_capture() {
+        let mut previous = None;
+        assert!(!is_coalesced_repeat("alpha  beta", &mut previous, 2000));
+        assert!(is_coalesced_repeat("alpha beta\n", &mut previous, 2000));
+        assert!(!is_coalesced_repeat("gamma", &mut previous, 2000));
+        // A, B, A is a real re-copy
+        assert!(!is_coalesced_repeat("alpha beta", &mut previous, 2000));
+        assert!(!is_coalesced_repeat("alpha  beta", &mut None, 0));
+    }
+
+    #[test]
+    fn expand_path_expands_home() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_path("~"), home);
+        assert_eq!(expand_path("~/Documents"), PathBuf::from(format!("{}/Documents", home.display())));
+        // Only a leading tilde means home
+        assert_eq!(expand_path("notes~/a"), PathBuf::from("notes~/a"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn expand_path_expands_posix_vars() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(expand_path("$HOME/Documents"), PathBuf::from(format!("{}/Documents", home)));
+        assert_eq!(expand_path("${HOME}/Documents"), PathBuf::from(format!("{}/Documents", home)));
+        // Unknown variables stay as written so the error names them
+        assert_eq!(
+            expand_path("$PATHFINDER_UNSET_TEST_VAR/x"),
+            PathBuf::from("$PATHFINDER_UNSET_TEST_VAR/x")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn expand_path_expands_windows_vars() {
+        let profile = std::env::var("USERPROFILE").unwrap();
+        assert_eq!(
+            expand_path("%USERPROFILE%\\Documents"),
+            PathBuf::from(format!("{}\\Documents", profile))
+        );
+        assert_eq!(
+            expand_path("%PATHFINDER_UNSET_TEST_VAR%\\x"),
+            PathBuf::from("%PATHFINDER_UNSET_TEST_VAR%\\x")
+        );
+        // A lone percent sign isn't a variable
+        assert_eq!(expand_path("C:\\100%"), PathBuf::from("C:\\100%"));
+    }
+
+    #[test]
+    fn launch_paths_with_shell_characters_stay_one_argument() {
+        let dir = std::env::temp_dir().join(format!("pathfinder-launch-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut names = vec!["a & calc.txt", "c %PATH% ^d.txt", "e; $(id) `id`.txt", "h,i, j.txt"];
+        if cfg!(not(target_os = "windows")) {
+            // Quotes and pipes aren't legal in Windows file names; validate_launch_path rejects quotes there
+            names.extend(["b | rm -rf x.txt", "f\" & calc & \"g.txt"]);
+        }
+
+        for name in names {
+            let path = dir.join(name);
+            fs::write(&path, "").unwrap();
+            let validated = validate_launch_path(&path.to_string_lossy()).unwrap();
+            assert_eq!(validated, path, "{} was altered", name);
+
+            // Windows opens through ShellExecute, which takes the path as a single string
+            #[cfg(not(target_os = "windows"))]
+            {
+                let command = open_command(&validated, false);
+                let program = command.get_program().to_string_lossy().to_lowercase();
+                assert!(!["sh", "bash", "cmd", "cmd.exe", "powershell"].contains(&program.as_str()));
+                let args: Vec<&std::ffi::OsStr> = command.get_args().collect();
+                assert_eq!(args, [path.as_os_str()], "{} was split or altered", name);
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn launch_path_rejects_option_like_and_multiline_paths() {
+        assert!(validate_launch_path("").is_err());
+        assert!(validate_launch_path("-a Calculator").is_err());
+        assert!(validate_launch_path("/tmp/x\n/usr/bin/id").is_err());
+        assert!(validate_launch_path("/definitely/not/here & id").is_err());
+        if cfg!(target_os = "windows") {
+            assert!(validate_launch_path("C:\\x\" & calc & \".txt").is_err());
+        }
+    }
+
+    #[test]
+    fn adaptive_mixed_audio_maps_each_split_label_once() {
+        let args = ffmpeg_output_map_args(true, None, None, Some("0:a"), Some("1:a"));
+        let graph = &args[args.iter().position(|a| a == "-filter_complex").unwrap() + 1];
+        let labels: Vec<String> = (0..HLS_RENDITIONS.len()).map(|i| format!("[aout{}]", i)).collect();
+        assert!(graph.ends_with(&format!(
+            "[0:a][1:a]amix=inputs=2:duration=longest,asplit={}{}",
+            HLS_RENDITIONS.len(),
+            labels.concat()
+        )));
+
+        let mapped: Vec<&String> = args.windows(2).filter(|w| w[0] == "-map").map(|w| &w[1]).collect();
+        for label in &labels {
+            assert_eq!(mapped.iter().filter(|m| **m == label).count(), 1, "{} mapped more than once", label);
+        }
+        assert!(!mapped.iter().any(|m| *m == "[aout]"));
+    }
+
+    #[test]
+    fn window_crop_is_an_output_filter() {
+        let args = ffmpeg_output_map_args(false, Some("crop=100:100:0:0"), Some("format=nv12,hwupload"), None, Some("1:a"));
+        let vf: Vec<&String> = args.windows(2).filter(|w| w[0] == "-vf").map(|w| &w[1]).collect();
+        assert_eq!(vf, ["crop=100:100:0:0,format=nv12,hwupload"]);
+        assert!(args.windows(2).any(|w| w[0] == "-map" && w[1] == "1:a"));
+    }
+
+    #[test]
+    fn only_fatal_ffmpeg_lines_stop_startup() {
+        assert!(is_fatal_ffmpeg_line("Error opening input file 1:none."));
+        assert!(is_fatal_ffmpeg_line("Conversion failed!"));
+        // Seen in the logs of streams that are working fine
+        assert!(!is_fatal_ffmpeg_line("[hls @ 0x7f8] Failed to delete old segment seg_000001.ts: No such file or directory"));
+        assert!(!is_fatal_ffmpeg_line("[h264 @ 0x7f8] error while decoding MB 12 4: Invalid argument"));
+    }
+}